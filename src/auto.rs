@@ -0,0 +1,267 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::cleaner::{BranchAction, BranchOutcome};
+use crate::git::{Branch, UpstreamStatus};
+use crate::protection::glob_match;
+use crate::services::git_repos_list_service::BranchStatus;
+use crate::ui::Prompt;
+
+/// What `--auto` does with a branch in a given status.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AutoRule {
+    pub action: BranchAction,
+    /// Only for branches matching one of these glob patterns; every branch if empty.
+    #[serde(default)]
+    pub branches: Vec<String>,
+    /// Only for branches whose changes are already in the default branch.
+    #[serde(default)]
+    pub only_if_merged: bool,
+}
+
+pub type AutoRules = BTreeMap<BranchStatus, AutoRule>;
+
+/// Answers the cleaner's prompts by the rules instead of asking. A branch without a
+/// rule that applies to it is left alone, except that it is still fast-forwarded when
+/// its upstream is simply ahead, unless a rule for that status says otherwise.
+#[derive(Debug, Clone, Default)]
+pub struct AutoPrompt {
+    rules: AutoRules,
+}
+
+impl AutoPrompt {
+    pub fn new(rules: AutoRules) -> Self {
+        Self { rules }
+    }
+
+    /// The rule for `branch`, if there is one for its status. A merged branch goes by
+    /// the rule for `Merged` if there is one, and otherwise by the one for the status
+    /// of its upstream.
+    fn rule_for(&self, branch: &Branch) -> Option<&AutoRule> {
+        let merged = branch
+            .merged_into
+            .is_some()
+            .then(|| self.rules.get(&BranchStatus::Merged))
+            .flatten();
+        merged.or_else(|| self.rules.get(&upstream_status(branch)))
+    }
+
+    /// The action the rules call for, if any rule applies to `branch`.
+    fn action_for(&self, branch: &Branch) -> Option<BranchAction> {
+        let rule = self.rule_for(branch)?;
+        let matches_branch = rule.branches.is_empty()
+            || rule
+                .branches
+                .iter()
+                .any(|pattern| glob_match(pattern, &branch.refname));
+        let merged_enough = !rule.only_if_merged || branch.merged_into.is_some();
+        (matches_branch && merged_enough).then_some(rule.action)
+    }
+}
+
+impl Prompt for AutoPrompt {
+    fn select(&self, message: &str, _options: &[String]) -> Result<usize> {
+        Err(anyhow!("cannot prompt in --auto mode: {message}"))
+    }
+
+    /// Picks the action the rules call for when it is on offer, treating "Delete it"
+    /// and "Delete (already merged)" as the same, and "Do nothing" otherwise. Actions
+    /// that only show something, and custom actions, are never picked.
+    fn select_action(
+        &self,
+        message: &str,
+        branch: &Branch,
+        actions: &[Option<BranchAction>],
+        _options: &[String],
+    ) -> Result<usize> {
+        let position =
+            |wanted: BranchAction| actions.iter().position(|action| *action == Some(wanted));
+        let chosen = match self.action_for(branch) {
+            Some(action) if action.only_shows() => None,
+            None => None,
+            Some(BranchAction::Delete | BranchAction::DeleteMerged) => {
+                position(BranchAction::DeleteMerged).or_else(|| position(BranchAction::Delete))
+            }
+            Some(action) => position(action),
+        };
+        chosen
+            .or_else(|| position(BranchAction::Nothing))
+            .ok_or_else(|| anyhow!("no way to leave the branch alone: {message}"))
+    }
+
+    fn allow_automatic(&self, branch: &Branch, action: BranchAction) -> bool {
+        match self.rule_for(branch) {
+            Some(_) => self.action_for(branch) == Some(action),
+            None => true,
+        }
+    }
+}
+
+/// The status of a branch relative to its upstream, whether or not it is merged.
+fn upstream_status(branch: &Branch) -> BranchStatus {
+    match branch.upstream.as_ref().map(|upstream| upstream.status) {
+        None => BranchStatus::NoUpstream,
+        Some(UpstreamStatus::Identical) => BranchStatus::Identical,
+        Some(UpstreamStatus::UpstreamIsAheadOfLocal) => BranchStatus::UpstreamAhead,
+        Some(UpstreamStatus::LocalIsAheadOfUpstream) => BranchStatus::LocalAhead,
+        Some(UpstreamStatus::MergeNeeded) => BranchStatus::Diverged,
+        Some(UpstreamStatus::UpstreamIsGone) => BranchStatus::UpstreamGone,
+    }
+}
+
+fn section_title(outcome: &BranchOutcome) -> &'static str {
+    match outcome {
+        BranchOutcome::Done(_) => "Done",
+        BranchOutcome::NeedsHuman(_) => "Needs a human",
+        BranchOutcome::Failed(_) => "Failed",
+    }
+}
+
+/// What an `--auto` run did, printed at the end.
+#[derive(Debug, Default)]
+pub struct AutoReport {
+    entries: Vec<(String, BranchOutcome)>,
+}
+
+impl AutoReport {
+    pub fn add(&mut self, repo: &Path, outcomes: Vec<(String, BranchOutcome)>) {
+        self.entries.extend(
+            outcomes
+                .into_iter()
+                .map(|(branch, outcome)| (format!("{}:{branch}", repo.display()), outcome)),
+        );
+    }
+
+    /// Notes something about a whole repository, such as it not being a directory.
+    pub fn add_repo(&mut self, repo: &Path, outcome: BranchOutcome) {
+        self.entries.push((repo.display().to_string(), outcome));
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.entries
+            .iter()
+            .any(|(_, outcome)| matches!(outcome, BranchOutcome::Failed(_)))
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        if self.entries.is_empty() {
+            return vec!["Nothing needed doing.".to_string()];
+        }
+        let mut lines = Vec::new();
+        for title in ["Done", "Needs a human", "Failed"] {
+            let section: Vec<String> = self
+                .entries
+                .iter()
+                .filter(|(_, outcome)| section_title(outcome) == title)
+                .map(|(location, outcome)| format!("  {location}: {}", outcome.detail()))
+                .collect();
+            if !section.is_empty() {
+                lines.push(format!("{title} ({}):", section.len()));
+                lines.extend(section);
+            }
+        }
+        lines
+    }
+
+    pub fn print(&self) {
+        println!();
+        for line in self.lines() {
+            println!("{line}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cleaner::{self, GitCleaner};
+    use crate::git::GitRepo;
+    use crate::git::backend::FakeBackend;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    fn branch(refname: &str, status: Option<UpstreamStatus>, merged: bool) -> Branch {
+        Branch {
+            merged_into: merged.then(|| "main".into()),
+            ..cleaner::tests::branch(refname, status)
+        }
+    }
+
+    fn rules(toml: &str) -> AutoRules {
+        toml::from_str(toml).expect("invalid rules")
+    }
+
+    #[test]
+    fn rules_pick_actions_by_status_pattern_and_merge() -> Result<()> {
+        let prompt = AutoPrompt::new(rules(
+            r#"
+            UpstreamAhead = { action = "Nothing" }
+            UpstreamGone = { action = "Delete", only_if_merged = true }
+            "#,
+        ));
+        let actions = [
+            Some(BranchAction::Delete),
+            Some(BranchAction::Log),
+            Some(BranchAction::Nothing),
+        ];
+        let gone = Some(UpstreamStatus::UpstreamIsGone);
+
+        assert_eq!(
+            prompt.select_action("", &branch("done", gone, true), &actions, &[])?,
+            0
+        );
+        assert_eq!(
+            prompt.select_action("", &branch("wip", gone, false), &actions, &[])?,
+            2
+        );
+        let behind = Some(UpstreamStatus::UpstreamIsAheadOfLocal);
+        assert!(!prompt.allow_automatic(&branch("stale", behind, false), BranchAction::Rebase));
+        assert!(
+            AutoPrompt::default()
+                .allow_automatic(&branch("stale", behind, false), BranchAction::Rebase)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unattended_run_reports_each_branch() {
+        let fake = Arc::new(FakeBackend::new());
+        fake.fail("git push origin my/broken");
+        let repo = GitRepo::with_backend(PathBuf::from("/tmp/repo"), fake.clone());
+        let ahead = Some(UpstreamStatus::LocalIsAheadOfUpstream);
+        let cleaner = GitCleaner::new(AutoPrompt::new(rules(
+            r#"LocalAhead = { action = "Push", branches = ["my/*"] }"#,
+        )));
+
+        let mut report = AutoReport::default();
+        report.add(
+            repo.dir(),
+            cleaner.handle_unattended(
+                &repo,
+                vec![
+                    branch("my/topic", ahead, false),
+                    branch("theirs", ahead, false),
+                    branch("my/broken", ahead, false),
+                ],
+            ),
+        );
+
+        assert_eq!(
+            fake.invocations(),
+            ["git push origin my/topic", "git push origin my/broken"]
+        );
+        let lines = report.lines();
+        assert_eq!(
+            lines[..2],
+            ["Done (1):", "  /tmp/repo:my/topic: Push to origin"]
+        );
+        assert_eq!(lines[2], "Needs a human (1):");
+        assert!(lines[3].starts_with("  /tmp/repo:theirs: "));
+        assert_eq!(lines[4], "Failed (1):");
+        assert!(report.has_failures());
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow, bail};
+
+use crate::cache::now_unix;
+use crate::git::backend::SharedBackend;
+use crate::git::{GitRepo, Identity};
+use crate::protection::glob_match;
+use crate::services::git_repos_list_service::{BranchListEntry, BranchStatus};
+
+/// Stands for whoever commits in the branch's repository in `--committer`.
+const ME: &str = "me";
+
+/// Which branches `repos --list` shows. Every condition that is set has to hold.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BranchFilter {
+    /// Any of these statuses; any status if empty.
+    pub statuses: Vec<BranchStatus>,
+    /// A glob pattern for the repository name, such as `org/*`.
+    pub repo: Option<String>,
+    /// A glob pattern for the branch name.
+    pub branch: Option<String>,
+    /// Part of the committer's name, or `me` for a committer with the repository's
+    /// `user.name` or `user.email`.
+    pub committer: Option<String>,
+    /// Only branches whose latest commit is at least this many seconds old.
+    pub older_than: Option<i64>,
+    /// Only branches whose latest commit is less than this many seconds old.
+    pub newer_than: Option<i64>,
+}
+
+impl BranchFilter {
+    /// The entries that pass, looking up who `me` is in each repository as needed.
+    pub fn apply(
+        &self,
+        entries: Vec<BranchListEntry>,
+        backend: &SharedBackend,
+    ) -> Vec<BranchListEntry> {
+        if *self == Self::default() {
+            return entries;
+        }
+        let now = now_unix();
+        let mut identities: HashMap<PathBuf, Identity> = HashMap::new();
+        entries
+            .into_iter()
+            .filter(|entry| {
+                let me = self.committer.as_deref() == Some(ME);
+                let identity = me.then(|| {
+                    identities
+                        .entry(entry.repo_path.clone())
+                        .or_insert_with(|| {
+                            GitRepo::with_backend(entry.repo_path.clone(), backend.clone())
+                                .identity()
+                        })
+                        .clone()
+                });
+                self.matches(entry, now, identity.as_ref())
+            })
+            .collect()
+    }
+
+    /// Whether `entry` passes at time `now`, with `me` being who commits in its
+    /// repository.
+    fn matches(&self, entry: &BranchListEntry, now: i64, me: Option<&Identity>) -> bool {
+        let age = now - entry.commit_timestamp;
+        (self.statuses.is_empty() || self.statuses.contains(&entry.status))
+            && self
+                .repo
+                .as_ref()
+                .is_none_or(|pattern| glob_match(pattern, &entry.repo_name))
+            && self
+                .branch
+                .as_ref()
+                .is_none_or(|pattern| glob_match(pattern, &entry.refname))
+            && self
+                .committer
+                .as_ref()
+                .is_none_or(|committer| committed_by(entry, committer, me))
+            && self.older_than.is_none_or(|older_than| age >= older_than)
+            && self.newer_than.is_none_or(|newer_than| age < newer_than)
+    }
+}
+
+fn committed_by(entry: &BranchListEntry, committer: &str, me: Option<&Identity>) -> bool {
+    if committer == ME {
+        let Some(me) = me else {
+            return false;
+        };
+        return me
+            .name
+            .as_ref()
+            .is_some_and(|name| name.eq_ignore_ascii_case(&entry.committer))
+            || me
+                .email
+                .as_ref()
+                .is_some_and(|email| email.eq_ignore_ascii_case(&entry.committer_email));
+    }
+    entry
+        .committer
+        .to_lowercase()
+        .contains(&committer.to_lowercase())
+}
+
+impl FromStr for BranchStatus {
+    type Err = anyhow::Error;
+
+    /// Reads a status by its label, such as `behind`, or by its name, such as
+    /// `UpstreamAhead`.
+    fn from_str(text: &str) -> Result<Self> {
+        let wanted = text.trim().to_lowercase().replace('-', " ");
+        [
+            Self::Identical,
+            Self::UpstreamAhead,
+            Self::LocalAhead,
+            Self::Diverged,
+            Self::UpstreamGone,
+            Self::NoUpstream,
+            Self::Merged,
+        ]
+        .into_iter()
+        .find(|status| {
+            status.label() == wanted || format!("{status:?}").to_lowercase() == wanted
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "unknown status '{text}'; expected ok, behind, ahead, diverged, gone, no-upstream or merged"
+            )
+        })
+    }
+}
+
+/// Reads an age such as `90d`, in hours (`h`), days (`d`), weeks (`w`) or years (`y`),
+/// as seconds.
+pub fn parse_age(text: &str) -> Result<i64> {
+    let text = text.trim();
+    let split = text.len() - text.chars().last().map_or(0, char::len_utf8);
+    let (count, unit) = text.split_at(split);
+    let count: u64 = count.parse().map_err(|_| {
+        anyhow!("invalid age '{text}'; expected a whole number and a unit, such as 90d")
+    })?;
+    let unit_secs: i64 = match unit {
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        "y" => 365 * 24 * 60 * 60,
+        _ => bail!("invalid age '{text}'; the unit must be h, d, w or y"),
+    };
+    i64::try_from(count)
+        .ok()
+        .and_then(|count| count.checked_mul(unit_secs))
+        .ok_or_else(|| anyhow!("age '{text}' is too long"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn entry(repo: &str, refname: &str, status: BranchStatus, age_days: i64) -> BranchListEntry {
+        BranchListEntry {
+            repo_name: repo.to_string(),
+            repo_path: PathBuf::from("/work").join(repo),
+            refname: refname.to_string(),
+            status,
+            ahead: 0,
+            behind: 0,
+            commit_timestamp: 1000 * DAY - age_days * DAY,
+            commit_date: "2024-01-01".to_string(),
+            committer: "Alice Smith".to_string(),
+            committer_email: "alice@example.com".to_string(),
+            author: "alice".to_string(),
+            author_email: "alice@example.com".to_string(),
+            author_timestamp: 1000,
+            author_date: "2024-01-01".to_string(),
+            subject: "Add feature".to_string(),
+            worktree_path: None,
+            protected: false,
+            pull_request: None,
+        }
+    }
+
+    #[test]
+    fn filter_combines_every_condition() {
+        let filter = BranchFilter {
+            statuses: vec![BranchStatus::UpstreamAhead, BranchStatus::UpstreamGone],
+            repo: Some("org/*".into()),
+            branch: Some("feature/*".into()),
+            committer: Some("alice".into()),
+            older_than: Some(90 * DAY),
+            newer_than: Some(365 * DAY),
+        };
+        let now = 1000 * DAY;
+        let stale = entry("org/api", "feature/x", BranchStatus::UpstreamGone, 100);
+        assert!(filter.matches(&stale, now, None));
+
+        let fresh = entry("org/api", "feature/x", BranchStatus::UpstreamGone, 10);
+        let ancient = entry("org/api", "feature/x", BranchStatus::UpstreamGone, 400);
+        let ahead = entry("org/api", "feature/x", BranchStatus::LocalAhead, 100);
+        let elsewhere = entry("other/api", "feature/x", BranchStatus::UpstreamGone, 100);
+        let main = entry("org/api", "main", BranchStatus::UpstreamGone, 100);
+        for entry in [fresh, ancient, ahead, elsewhere, main] {
+            assert!(!filter.matches(&entry, now, None), "{entry:?}");
+        }
+    }
+
+    #[test]
+    fn me_is_who_commits_in_the_repository() {
+        let filter = BranchFilter {
+            committer: Some(ME.into()),
+            ..BranchFilter::default()
+        };
+        let entry = entry("repo", "feature", BranchStatus::Identical, 0);
+        let identity = |name: &str| Identity {
+            name: Some(name.into()),
+            email: None,
+        };
+        assert!(filter.matches(&entry, 0, Some(&identity("alice smith"))));
+        assert!(!filter.matches(&entry, 0, Some(&identity("Bob"))));
+        let by_email = Identity {
+            name: Some("A. Smith".into()),
+            email: Some("Alice@Example.com".into()),
+        };
+        assert!(filter.matches(&entry, 0, Some(&by_email)));
+        assert!(!filter.matches(&entry, 0, Some(&Identity::default())));
+    }
+
+    #[test]
+    fn statuses_and_ages_are_parsed() -> Result<()> {
+        assert_eq!(
+            "behind".parse::<BranchStatus>()?,
+            BranchStatus::UpstreamAhead
+        );
+        assert_eq!(
+            "no-upstream".parse::<BranchStatus>()?,
+            BranchStatus::NoUpstream
+        );
+        assert_eq!("Merged".parse::<BranchStatus>()?, BranchStatus::Merged);
+        assert!("stale".parse::<BranchStatus>().is_err());
+
+        assert_eq!(parse_age("90d")?, 90 * DAY);
+        assert_eq!(parse_age("1w")?, 7 * DAY);
+        assert!(parse_age("90").is_err());
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("-5d").is_err());
+        assert!(parse_age("99999999999999y").is_err());
+        Ok(())
+    }
+}
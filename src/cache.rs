@@ -5,48 +5,69 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
+use crate::forge::PullRequests;
 use crate::services::git_repos_list_service::BranchListEntry;
 
-const CACHE_TTL_SECS: i64 = 3600;
+const DEFAULT_TTL_SECS: i64 = 3600;
+
+/// Goes up whenever what is cached for a branch listing changes, so that older cache
+/// files are scanned again rather than shown with fields missing.
+const BRANCH_CACHE_VERSION: u32 = 1;
+
+/// Goes up whenever what is cached for pull requests changes, so that older cache files
+/// are looked up again rather than misread.
+const PULL_REQUEST_CACHE_VERSION: u32 = 1;
 
 pub struct BranchCache {
     root: PathBuf,
+    ttl_secs: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct StoredCache {
-    invocation_path: PathBuf,
+    version: u32,
+    roots: Vec<PathBuf>,
     timestamp: i64,
     entries: Vec<BranchListEntry>,
 }
 
 impl BranchCache {
     pub fn new(root: PathBuf) -> Self {
-        Self { root }
+        Self {
+            root,
+            ttl_secs: DEFAULT_TTL_SECS,
+        }
     }
 
     pub fn from_env() -> Option<Self> {
         default_cache_root().map(Self::new)
     }
 
-    pub fn read_fresh(&self, path: &Path) -> Option<Vec<BranchListEntry>> {
-        let cache_path = self.cache_file_for(path);
+    /// How old a cached scan may be and still be used.
+    pub fn with_ttl(mut self, ttl_secs: i64) -> Self {
+        self.ttl_secs = ttl_secs;
+        self
+    }
+
+    pub fn read_fresh(&self, roots: &[PathBuf]) -> Option<Vec<BranchListEntry>> {
+        let cache_path = self.cache_file_for(roots);
         let content = fs::read_to_string(&cache_path).ok()?;
         let cache: StoredCache = serde_json::from_str(&content).ok()?;
-        if now_unix() - cache.timestamp > CACHE_TTL_SECS {
+        if cache.version != BRANCH_CACHE_VERSION || now_unix() - cache.timestamp > self.ttl_secs {
             return None;
         }
         Some(cache.entries)
     }
 
-    pub fn write(&self, path: &Path, entries: &[BranchListEntry]) -> Result<()> {
-        let cache_path = self.cache_file_for(path);
+    pub fn write(&self, roots: &[PathBuf], entries: &[BranchListEntry]) -> Result<()> {
+        let cache_path = self.cache_file_for(roots);
         if let Some(parent) = cache_path.parent() {
             fs::create_dir_all(parent)
                 .with_context(|| format!("failed to create cache dir at {}", parent.display()))?;
         }
         let cache = StoredCache {
-            invocation_path: path.to_path_buf(),
+            version: BRANCH_CACHE_VERSION,
+            roots: roots.to_vec(),
             timestamp: now_unix(),
             entries: entries.to_vec(),
         };
@@ -56,12 +77,80 @@ impl BranchCache {
         Ok(())
     }
 
-    fn cache_file_for(&self, path: &Path) -> PathBuf {
-        let key = format!("{:016x}", hash_path(path));
+    fn cache_file_for(&self, roots: &[PathBuf]) -> PathBuf {
+        let key = format!("{:016x}", hash_roots(roots));
         self.root.join("branches").join(format!("{key}.json"))
     }
 }
 
+/// The pull requests looked up for each repository, so that they aren't asked for on
+/// every run.
+pub struct PullRequestCache {
+    root: PathBuf,
+    ttl_secs: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredPullRequests {
+    version: u32,
+    repo: PathBuf,
+    timestamp: i64,
+    pull_requests: PullRequests,
+}
+
+impl PullRequestCache {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            ttl_secs: DEFAULT_TTL_SECS,
+        }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        default_cache_root().map(Self::new)
+    }
+
+    /// How old cached pull requests may be and still be used.
+    pub fn with_ttl(mut self, ttl_secs: i64) -> Self {
+        self.ttl_secs = ttl_secs;
+        self
+    }
+
+    pub fn read_fresh(&self, repo: &Path) -> Option<PullRequests> {
+        let content = fs::read_to_string(self.cache_file_for(repo)).ok()?;
+        let cache: StoredPullRequests = serde_json::from_str(&content).ok()?;
+        if cache.version != PULL_REQUEST_CACHE_VERSION
+            || now_unix() - cache.timestamp > self.ttl_secs
+        {
+            return None;
+        }
+        Some(cache.pull_requests)
+    }
+
+    pub fn write(&self, repo: &Path, pull_requests: &PullRequests) -> Result<()> {
+        let cache_path = self.cache_file_for(repo);
+        if let Some(parent) = cache_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create cache dir at {}", parent.display()))?;
+        }
+        let cache = StoredPullRequests {
+            version: PULL_REQUEST_CACHE_VERSION,
+            repo: repo.to_path_buf(),
+            timestamp: now_unix(),
+            pull_requests: pull_requests.clone(),
+        };
+        let json = serde_json::to_string(&cache)?;
+        fs::write(&cache_path, json)
+            .with_context(|| format!("failed to write cache at {}", cache_path.display()))?;
+        Ok(())
+    }
+
+    fn cache_file_for(&self, repo: &Path) -> PathBuf {
+        let key = format!("{:016x}", fnv1a(repo.to_string_lossy().as_bytes()));
+        self.root.join("pulls").join(format!("{key}.json"))
+    }
+}
+
 fn default_cache_root() -> Option<PathBuf> {
     if let Ok(xdg) = std::env::var("XDG_CACHE_HOME") {
         if !xdg.is_empty() {
@@ -76,8 +165,13 @@ fn default_cache_root() -> Option<PathBuf> {
     )
 }
 
-fn hash_path(path: &Path) -> u64 {
-    fnv1a(path.to_string_lossy().as_bytes())
+fn hash_roots(roots: &[PathBuf]) -> u64 {
+    let joined = roots
+        .iter()
+        .map(|root| root.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("\0");
+    fnv1a(joined.as_bytes())
 }
 
 fn fnv1a(data: &[u8]) -> u64 {
@@ -89,7 +183,7 @@ fn fnv1a(data: &[u8]) -> u64 {
     hash
 }
 
-fn now_unix() -> i64 {
+pub fn now_unix() -> i64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .map(|d| d.as_secs() as i64)
@@ -99,6 +193,7 @@ fn now_unix() -> i64 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::forge::{PullRequest, PullRequestState, head_label};
     use crate::services::git_repos_list_service::BranchStatus;
 
     fn entry() -> BranchListEntry {
@@ -107,10 +202,20 @@ mod tests {
             repo_path: PathBuf::from("/tmp/repo"),
             refname: "main".to_string(),
             status: BranchStatus::Identical,
+            ahead: 0,
+            behind: 0,
             commit_timestamp: 1000,
             commit_date: "2024-01-01".to_string(),
             committer: "alice".to_string(),
+            committer_email: "alice@example.com".to_string(),
+            author: "alice".to_string(),
+            author_email: "alice@example.com".to_string(),
+            author_timestamp: 1000,
+            author_date: "2024-01-01".to_string(),
+            subject: "Add feature".to_string(),
             worktree_path: None,
+            protected: false,
+            pull_request: None,
         }
     }
 
@@ -118,9 +223,9 @@ mod tests {
     fn write_then_read_round_trips_entries() -> Result<()> {
         let temp = tempfile::tempdir()?;
         let cache = BranchCache::new(temp.path().to_path_buf());
-        let invocation = PathBuf::from("/tmp/some/projects");
-        cache.write(&invocation, &[entry()])?;
-        let read = cache.read_fresh(&invocation).expect("expected fresh cache");
+        let roots = [PathBuf::from("/tmp/some/projects")];
+        cache.write(&roots, &[entry()])?;
+        let read = cache.read_fresh(&roots).expect("expected fresh cache");
         assert_eq!(read.len(), 1);
         assert_eq!(read[0].refname, "main");
         Ok(())
@@ -130,16 +235,34 @@ mod tests {
     fn stale_cache_is_ignored() -> Result<()> {
         let temp = tempfile::tempdir()?;
         let cache = BranchCache::new(temp.path().to_path_buf());
-        let invocation = PathBuf::from("/tmp/another/projects");
-        let cache_path = cache.cache_file_for(&invocation);
+        let roots = [PathBuf::from("/tmp/another/projects")];
+        let cache_path = cache.cache_file_for(&roots);
         fs::create_dir_all(cache_path.parent().unwrap())?;
         let stale = StoredCache {
-            invocation_path: invocation.clone(),
-            timestamp: now_unix() - CACHE_TTL_SECS - 60,
+            version: BRANCH_CACHE_VERSION,
+            roots: roots.to_vec(),
+            timestamp: now_unix() - DEFAULT_TTL_SECS - 60,
             entries: vec![entry()],
         };
         fs::write(&cache_path, serde_json::to_string(&stale)?)?;
-        assert!(cache.read_fresh(&invocation).is_none());
+        assert!(cache.read_fresh(&roots).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn cache_from_before_versioning_is_ignored() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let cache = BranchCache::new(temp.path().to_path_buf());
+        let roots = [PathBuf::from("/tmp/old/projects")];
+        let cache_path = cache.cache_file_for(&roots);
+        fs::create_dir_all(cache_path.parent().unwrap())?;
+        let old = serde_json::json!({
+            "roots": roots,
+            "timestamp": now_unix(),
+            "entries": [],
+        });
+        fs::write(&cache_path, old.to_string())?;
+        assert!(cache.read_fresh(&roots).is_none());
         Ok(())
     }
 
@@ -147,6 +270,45 @@ mod tests {
     fn missing_cache_returns_none() {
         let temp = tempfile::tempdir().unwrap();
         let cache = BranchCache::new(temp.path().to_path_buf());
-        assert!(cache.read_fresh(Path::new("/tmp/no/cache/here/yet")).is_none());
+        assert!(
+            cache
+                .read_fresh(&[PathBuf::from("/tmp/no/cache/here/yet")])
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn ttl_is_configurable() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let roots = [PathBuf::from("/tmp/a"), PathBuf::from("/tmp/b")];
+        let cache = BranchCache::new(temp.path().to_path_buf()).with_ttl(-1);
+        cache.write(&roots, &[entry()])?;
+        assert!(cache.read_fresh(&roots).is_none());
+        assert!(cache.with_ttl(60).read_fresh(&roots).is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn pull_requests_are_cached_per_repository() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let cache = PullRequestCache::new(temp.path().to_path_buf());
+        let pull_requests = PullRequests::from([(
+            head_label("o", "feature"),
+            PullRequest {
+                number: 12,
+                state: PullRequestState::Merged,
+                url: "https://github.com/o/r/pull/12".to_string(),
+                review: None,
+                head_sha: None,
+            },
+        )]);
+        cache.write(Path::new("/tmp/repo"), &pull_requests)?;
+        assert_eq!(
+            cache.read_fresh(Path::new("/tmp/repo")),
+            Some(pull_requests)
+        );
+        assert_eq!(cache.read_fresh(Path::new("/tmp/other")), None);
+        assert_eq!(cache.with_ttl(-1).read_fresh(Path::new("/tmp/repo")), None);
+        Ok(())
     }
 }
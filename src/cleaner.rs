@@ -2,27 +2,51 @@ use std::fs;
 use std::path::{Path, PathBuf};
 
 use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
 
-use crate::git::{Branch, GitRepo, UpstreamStatus};
+use crate::cache::now_unix;
+use crate::config::{CustomAction, DefaultActions};
+use crate::forge::{PullRequest, PullRequestState};
+use crate::git::{Branch, GitRepo, SyncStrategy, Upstream, UpstreamStatus};
+use crate::journal::{Journal, record};
+use crate::services::git_repos_list_service::branch_status;
 use crate::task_result::TaskResult;
 use crate::ui::Prompt;
 
 #[derive(Clone)]
 pub struct GitCleaner<P: Prompt> {
     prompt: P,
-    dry_run: bool,
+    journal: Option<Journal>,
+    default_actions: DefaultActions,
+    custom_actions: Vec<CustomAction>,
 }
 
 impl<P: Prompt> GitCleaner<P> {
     pub fn new(prompt: P) -> Self {
         Self {
             prompt,
-            dry_run: false,
+            journal: None,
+            default_actions: DefaultActions::new(),
+            custom_actions: Vec::new(),
         }
     }
 
-    pub fn new_with_dry_run(prompt: P, dry_run: bool) -> Self {
-        Self { prompt, dry_run }
+    /// Offers these actions first for branches in the given statuses.
+    pub fn with_default_actions(mut self, default_actions: DefaultActions) -> Self {
+        self.default_actions = default_actions;
+        self
+    }
+
+    /// Also offers these actions, in the menus for the statuses they are for.
+    pub fn with_custom_actions(mut self, custom_actions: Vec<CustomAction>) -> Self {
+        self.custom_actions = custom_actions;
+        self
+    }
+
+    /// Records every action that changes something in `journal`.
+    pub fn with_journal(mut self, journal: Option<Journal>) -> Self {
+        self.journal = journal;
+        self
     }
 
     pub fn handle(&self, repo: &GitRepo, branches: Vec<Branch>) -> Result<TaskResult> {
@@ -36,119 +60,67 @@ impl<P: Prompt> GitCleaner<P> {
         Ok(result)
     }
 
-    pub fn handle_branch(&self, repo: &GitRepo, branch: &Branch) -> Result<TaskResult> {
-        if let Some(upstream) = &branch.upstream {
-            match upstream.status {
-                UpstreamStatus::Identical => Ok(TaskResult::Proceed),
-                UpstreamStatus::UpstreamIsAheadOfLocal => {
-                    if let Some(path) = worktree_elsewhere_path(branch, repo) {
-                        if self.dry_run {
-                            println!(
-                                "[DRY RUN] {}: Upstream is ahead (checked out elsewhere)",
-                                branch.refname
-                            );
-                            Ok(TaskResult::Proceed)
-                        } else {
-                            print_worktree_redirect(branch, &path);
-                            Ok(TaskResult::ShellActionRequired(path))
-                        }
-                    } else {
-                        if self.dry_run {
-                            println!(
-                                "[DRY RUN] {}: Upstream is ahead, would rebase",
-                                branch.refname
-                            );
-                            Ok(TaskResult::Proceed)
-                        } else {
-                            repo.rebase(&branch.refname, &upstream.name)?;
-                            Ok(TaskResult::Proceed)
-                        }
-                    }
-                }
-                UpstreamStatus::LocalIsAheadOfUpstream => {
-                    if self.dry_run {
-                        println!("[DRY RUN] {}: Branch is ahead of upstream", branch.refname);
-                        Ok(TaskResult::Proceed)
-                    } else {
-                        self.select_action(
-                            repo,
-                            branch,
-                            "Branch is ahead of upstream",
-                            &[
-                                BranchAction::Push,
-                                BranchAction::Log,
-                                BranchAction::Shell,
-                                BranchAction::Nothing,
-                            ],
-                        )
-                    }
-                }
-                UpstreamStatus::MergeNeeded => {
-                    if self.dry_run {
-                        println!(
-                            "[DRY RUN] {}: Different commits on local and upstream",
-                            branch.refname
-                        );
-                        Ok(TaskResult::Proceed)
-                    } else {
-                        self.select_action(
-                            repo,
-                            branch,
-                            "Different commits on local and upstream",
-                            &[
-                                BranchAction::Rebase,
-                                BranchAction::Log,
-                                BranchAction::Delete,
-                                BranchAction::Shell,
-                                BranchAction::Nothing,
-                            ],
-                        )
-                    }
+    /// Like [`Self::handle`], but goes on to the next branch where a real run would
+    /// stop and exit to a shell. Meant for dry runs, where nothing has changed.
+    pub fn preview(&self, repo: &GitRepo, branches: Vec<Branch>) -> Result<()> {
+        for branch in branches {
+            if let TaskResult::ShellActionRequired(path) = self.handle_branch(repo, &branch)? {
+                println!("[DRY RUN] Would exit to shell in {}", path.display());
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`Self::handle`], for runs with no one to answer: goes on to the next
+    /// branch where an interactive run would stop, whether to exit to a shell or
+    /// because an action failed, and says what became of each branch that needed
+    /// action.
+    pub fn handle_unattended(
+        &self,
+        repo: &GitRepo,
+        branches: Vec<Branch>,
+    ) -> Vec<(String, BranchOutcome)> {
+        let mut outcomes = Vec::new();
+        for branch in branches {
+            let outcome = match self.step(repo, &branch) {
+                Ok(Step::NothingToDo) => continue,
+                Ok(Step::Declined(message)) => BranchOutcome::NeedsHuman(message),
+                Ok(Step::Performed(_, ActionResult::ExitToShell(path))) => {
+                    BranchOutcome::NeedsHuman(format!("needs to be handled in {}", path.display()))
                 }
-                UpstreamStatus::UpstreamIsGone => {
-                    if self.dry_run {
-                        println!(
-                            "[DRY RUN] {}: Upstream is set, but it is gone",
-                            branch.refname
-                        );
-                        Ok(TaskResult::Proceed)
-                    } else {
-                        let mut actions = vec![
-                            BranchAction::Delete,
-                            BranchAction::Log,
-                            BranchAction::Shell,
-                            BranchAction::Nothing,
-                        ];
-                        if branch_checked_out_elsewhere(branch, repo) {
-                            actions.insert(0, BranchAction::DeleteWorktreeAndBranch);
-                        }
-                        self.select_action(
-                            repo,
-                            branch,
-                            "Upstream is set, but it is gone",
-                            &actions,
-                        )
-                    }
+                Ok(Step::Performed(label, _)) => BranchOutcome::Done(label),
+                Err(error) => BranchOutcome::Failed(format!("{error:#}")),
+            };
+            outcomes.push((branch.refname, outcome));
+        }
+        outcomes
+    }
+
+    pub fn handle_branch(&self, repo: &GitRepo, branch: &Branch) -> Result<TaskResult> {
+        match self.step(repo, branch)? {
+            Step::Performed(_, ActionResult::ExitToShell(path)) => {
+                Ok(TaskResult::ShellActionRequired(path))
+            }
+            Step::NothingToDo | Step::Declined(_) | Step::Performed(..) => Ok(TaskResult::Proceed),
+        }
+    }
+
+    /// Proposes what to do with `branch` and, with the prompt's consent, does it.
+    fn step(&self, repo: &GitRepo, branch: &Branch) -> Result<Step> {
+        match propose(repo, branch, &self.default_actions) {
+            Proposal::Nothing => Ok(Step::NothingToDo),
+            Proposal::Automatic(action) => {
+                if !self.prompt.allow_automatic(branch, action) {
+                    return Ok(Step::Declined(format!(
+                        "{} isn't allowed",
+                        action.label(branch)
+                    )));
                 }
+                let result = self.perform_action(repo, branch, action)?;
+                Ok(Step::Performed(action.label(branch), result))
             }
-        } else {
-            if self.dry_run {
-                println!("[DRY RUN] {}: Branch has no upstream", branch.refname);
-                Ok(TaskResult::Proceed)
-            } else {
-                self.select_action(
-                    repo,
-                    branch,
-                    "Branch has no upstream",
-                    &[
-                        BranchAction::CreatePr,
-                        BranchAction::PushCreatingOrigin,
-                        BranchAction::Delete,
-                        BranchAction::Log,
-                        BranchAction::Shell,
-                        BranchAction::Nothing,
-                    ],
-                )
+            Proposal::Choice { message, actions } => {
+                self.select_action(repo, branch, &message, &actions)
             }
         }
     }
@@ -159,10 +131,12 @@ impl<P: Prompt> GitCleaner<P> {
         branch: &Branch,
         message: &str,
         actions: &[BranchAction],
-    ) -> Result<TaskResult> {
+    ) -> Result<Step> {
+        let items = self.menu(branch, actions);
+        let item_actions: Vec<Option<BranchAction>> = items.iter().map(MenuItem::action).collect();
         loop {
             if let Some(path) = branch.worktree_path.as_ref() {
-                print_worktree_hint(branch, path);
+                print_worktree_redirect(branch, path);
             }
             let repo_display = repo
                 .dir()
@@ -172,46 +146,136 @@ impl<P: Prompt> GitCleaner<P> {
                 .unwrap_or_else(|| repo.dir().to_string_lossy().into_owned());
 
             let prompt = format!("{}:{}: {}", repo_display, branch.refname, message);
-            let options: Vec<String> = actions
-                .iter()
-                .map(|action| action.description().to_string())
-                .collect();
+            let options: Vec<String> = items.iter().map(|item| item.label(branch)).collect();
 
-            let selected_index = self.prompt.select(&prompt, &options)?;
-            let action = actions
+            let selected_index =
+                self.prompt
+                    .select_action(&prompt, branch, &item_actions, &options)?;
+            let item = items
                 .get(selected_index)
-                .copied()
                 .ok_or_else(|| anyhow!("invalid selection index {selected_index}"))?;
 
-            match self.perform_action(repo, branch, action)? {
-                ActionResult::Handled => return Ok(TaskResult::Proceed),
-                ActionResult::NotHandled => continue,
-                ActionResult::ExitToShell(path) => {
-                    return Ok(TaskResult::ShellActionRequired(path));
+            let result = match item {
+                MenuItem::Action(BranchAction::Nothing) => {
+                    return Ok(Step::Declined(message.to_string()));
                 }
+                MenuItem::Action(action) => self.perform_action(repo, branch, *action)?,
+                MenuItem::Custom(custom) => self.perform_custom_action(repo, branch, custom)?,
+            };
+            match result {
+                ActionResult::NotHandled => continue,
+                result => return Ok(Step::Performed(item.label(branch), result)),
             }
         }
     }
 
-    fn perform_action(
+    /// The built-in `actions` followed by the custom actions for the branch's status,
+    /// keeping "Do nothing" last.
+    fn menu<'a>(&'a self, branch: &Branch, actions: &[BranchAction]) -> Vec<MenuItem<'a>> {
+        let mut items: Vec<MenuItem> = actions.iter().copied().map(MenuItem::Action).collect();
+        let status = branch_status(branch);
+        let custom = self
+            .custom_actions
+            .iter()
+            .filter(|custom| custom.statuses.is_empty() || custom.statuses.contains(&status))
+            .map(MenuItem::Custom);
+        let at = match items.last() {
+            Some(MenuItem::Action(BranchAction::Nothing)) => items.len() - 1,
+            _ => items.len(),
+        };
+        items.splice(at..at, custom);
+        items
+    }
+
+    /// Runs a custom action's command for `branch`, recording it in the journal.
+    fn perform_custom_action(
         &self,
+        repo: &GitRepo,
+        branch: &Branch,
+        custom: &CustomAction,
+    ) -> Result<ActionResult> {
+        let command = custom_command(&custom.command, repo, branch);
+        record(
+            self.journal.as_ref(),
+            repo,
+            &branch.refname,
+            custom.label.clone(),
+            Some(branch.tip.clone()),
+            |repo, _| {
+                repo.run_shell(&command)?;
+                Ok(if custom.handled {
+                    ActionResult::Handled
+                } else {
+                    ActionResult::NotHandled
+                })
+            },
+        )
+    }
+
+    /// Performs `action` on `branch`, recording it in the journal if it changed anything.
+    pub fn perform_action(
+        &self,
+        repo: &GitRepo,
+        branch: &Branch,
+        action: BranchAction,
+    ) -> Result<ActionResult> {
+        if branch.protected && action.is_destructive_for(branch) {
+            return Err(anyhow!(
+                "refusing to \"{}\" protected branch {}",
+                action.description(),
+                branch.refname
+            ));
+        }
+        record(
+            self.journal.as_ref(),
+            repo,
+            &branch.refname,
+            action.label(branch),
+            Some(branch.tip.clone()),
+            |repo, backup_ref| Self::run_action(repo, branch, action, backup_ref),
+        )
+    }
+
+    /// Sets `backup_ref` to where the old tip was kept before a destructive action.
+    fn run_action(
         repo: &GitRepo,
         branch: &Branch,
         action: BranchAction,
+        backup_ref: &mut Option<String>,
     ) -> Result<ActionResult> {
-        match action {
+        let result = match action {
             BranchAction::CreatePr => {
-                repo.push_creating_origin(&branch.refname)?;
-                repo.create_pull_request(&branch.refname)?;
-                Ok(ActionResult::Handled)
+                repo.push_creating_origin(&branch.refname, branch.push_remote())?;
+                repo.create_pull_request(
+                    &branch.refname,
+                    branch.push_remote(),
+                    &repo.default_branch()?,
+                )?;
+                ActionResult::Handled
+            }
+            BranchAction::CreateStackedPr => {
+                let parent = repo
+                    .stack_parent(&branch.refname)
+                    .ok_or_else(|| anyhow!("branch isn't stacked on another branch"))?;
+                repo.push_creating_origin(&branch.refname, branch.push_remote())?;
+                repo.create_pull_request(&branch.refname, branch.push_remote(), &parent)?;
+                ActionResult::Handled
+            }
+            BranchAction::OpenPr => {
+                let pull = branch
+                    .pull_request
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("branch has no pull request to open"))?;
+                repo.open_pull_request(pull.number)?;
+                ActionResult::NotHandled
             }
             BranchAction::Push => {
-                repo.push(&branch.refname)?;
-                Ok(ActionResult::Handled)
+                repo.push(&branch.refname, branch.push_remote())?;
+                ActionResult::Handled
             }
             BranchAction::PushCreatingOrigin => {
-                repo.push_creating_origin(&branch.refname)?;
-                Ok(ActionResult::Handled)
+                repo.push_creating_origin(&branch.refname, branch.push_remote())?;
+                ActionResult::Handled
             }
             BranchAction::Rebase => {
                 if let Some(path) = worktree_elsewhere_path(branch, repo) {
@@ -222,30 +286,45 @@ impl<P: Prompt> GitCleaner<P> {
                     .upstream
                     .as_ref()
                     .ok_or_else(|| anyhow!("branch has no upstream to rebase onto"))?;
+                *backup_ref = Some(back_up(repo, branch)?);
                 repo.rebase(&branch.refname, &upstream.name)?;
-                Ok(ActionResult::Handled)
+                ActionResult::Handled
+            }
+            BranchAction::Merge => {
+                if let Some(path) = worktree_elsewhere_path(branch, repo) {
+                    print_worktree_redirect(branch, &path);
+                    return Ok(ActionResult::ExitToShell(path));
+                }
+                let upstream = branch
+                    .upstream
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("branch has no upstream to merge"))?;
+                repo.merge(&branch.refname, &upstream.name)?;
+                ActionResult::Handled
             }
-            BranchAction::Delete => {
+            BranchAction::Delete | BranchAction::DeleteMerged => {
                 if let Some(path) = worktree_elsewhere_path(branch, repo) {
                     print_worktree_redirect(branch, &path);
                     return Ok(ActionResult::ExitToShell(path));
                 }
+                *backup_ref = Some(back_up(repo, branch)?);
                 repo.checkout_default_branch()?;
                 repo.delete_branch_forcefully(&branch.refname)?;
-                Ok(ActionResult::Handled)
+                ActionResult::Handled
             }
             BranchAction::DeleteWorktreeAndBranch => {
                 let path = branch
                     .worktree_path
                     .as_ref()
                     .ok_or_else(|| anyhow!("branch has no associated worktree to delete"))?;
+                *backup_ref = Some(back_up(repo, branch)?);
                 repo.delete_worktree(path)?;
                 repo.delete_branch_forcefully(&branch.refname)?;
-                Ok(ActionResult::Handled)
+                ActionResult::Handled
             }
             BranchAction::Log => {
                 repo.show_log(&branch.refname)?;
-                Ok(ActionResult::NotHandled)
+                ActionResult::NotHandled
             }
             BranchAction::Shell => {
                 if let Some(path) = worktree_elsewhere_path(branch, repo) {
@@ -253,20 +332,73 @@ impl<P: Prompt> GitCleaner<P> {
                     return Ok(ActionResult::ExitToShell(path));
                 }
                 repo.checkout_branch(&branch.refname)?;
-                Ok(ActionResult::ExitToShell(repo.dir().to_path_buf()))
+                ActionResult::ExitToShell(repo.dir().to_path_buf())
             }
-            BranchAction::Nothing => Ok(ActionResult::Handled),
+            BranchAction::Nothing => ActionResult::Handled,
+        };
+        Ok(result)
+    }
+}
+
+/// An entry in a branch's menu.
+enum MenuItem<'a> {
+    Action(BranchAction),
+    Custom(&'a CustomAction),
+}
+
+impl MenuItem<'_> {
+    /// The built-in action, if it is one.
+    fn action(&self) -> Option<BranchAction> {
+        match self {
+            MenuItem::Action(action) => Some(*action),
+            MenuItem::Custom(_) => None,
+        }
+    }
+
+    fn label(&self, branch: &Branch) -> String {
+        match self {
+            MenuItem::Action(action) => action.label(branch),
+            MenuItem::Custom(custom) => custom.label.clone(),
         }
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
-enum BranchAction {
+/// Fills in a custom action's placeholders, quoted for the shell. A branch without an
+/// upstream gets an empty `{upstream}`, and one that isn't checked out anywhere the
+/// repository as its `{worktree}`.
+fn custom_command(template: &str, repo: &GitRepo, branch: &Branch) -> String {
+    let upstream = branch
+        .upstream
+        .as_ref()
+        .map(|upstream| upstream.name.as_str())
+        .unwrap_or("");
+    let worktree = branch.worktree_path.as_deref().unwrap_or(repo.dir());
+    template
+        .replace("{repo}", &shell_quote(&repo.dir().to_string_lossy()))
+        .replace("{branch}", &shell_quote(&branch.refname))
+        .replace("{upstream}", &shell_quote(upstream))
+        .replace("{worktree}", &shell_quote(&worktree.to_string_lossy()))
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn back_up(repo: &GitRepo, branch: &Branch) -> Result<String> {
+    repo.create_backup_ref(&branch.refname, &branch.tip, now_unix())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BranchAction {
     Push,
     PushCreatingOrigin,
     CreatePr,
+    CreateStackedPr,
+    OpenPr,
     Rebase,
+    Merge,
     Delete,
+    DeleteMerged,
     DeleteWorktreeAndBranch,
     Log,
     Shell,
@@ -274,27 +406,399 @@ enum BranchAction {
 }
 
 impl BranchAction {
-    fn description(&self) -> &'static str {
+    pub fn description(&self) -> &'static str {
         match self {
             BranchAction::Push => "Push to origin",
             BranchAction::PushCreatingOrigin => "Push to create origin",
             BranchAction::CreatePr => "Push and create pull request",
+            BranchAction::CreateStackedPr => {
+                "Push and create pull request onto the branch it is stacked on"
+            }
+            BranchAction::OpenPr => "Open pull request in browser",
             BranchAction::Rebase => "Rebase onto origin",
+            BranchAction::Merge => "Merge origin into it",
             BranchAction::Delete => "Delete it",
+            BranchAction::DeleteMerged => "Delete (already merged)",
             BranchAction::DeleteWorktreeAndBranch => "Delete worktree and branch",
             BranchAction::Log => "Show git log",
             BranchAction::Shell => "Exit to shell with branch checked out",
             BranchAction::Nothing => "Do nothing",
         }
     }
+
+    fn creates_pull_request(&self) -> bool {
+        matches!(self, BranchAction::CreatePr | BranchAction::CreateStackedPr)
+    }
+
+    /// Whether the action only shows something and leaves the branch to be dealt
+    /// with again, so that a prompt answering by itself must never pick it.
+    pub fn only_shows(&self) -> bool {
+        matches!(
+            self,
+            BranchAction::Log | BranchAction::Shell | BranchAction::OpenPr
+        )
+    }
+
+    /// Whether the action deletes `branch` or rewrites its history. Rebasing a branch
+    /// that is simply behind its upstream is a fast-forward, so it doesn't count.
+    pub fn is_destructive_for(&self, branch: &Branch) -> bool {
+        match self {
+            BranchAction::Delete
+            | BranchAction::DeleteMerged
+            | BranchAction::DeleteWorktreeAndBranch => true,
+            BranchAction::Rebase => !matches!(
+                branch.upstream.as_ref().map(|upstream| upstream.status),
+                Some(UpstreamStatus::UpstreamIsAheadOfLocal)
+            ),
+            _ => false,
+        }
+    }
+
+    fn label(&self, branch: &Branch) -> String {
+        match (self, &branch.upstream, branch.merged_into.as_deref()) {
+            (BranchAction::Push, _, _) => format!("Push to {}", branch.push_remote()),
+            (BranchAction::PushCreatingOrigin, _, _) => {
+                format!("Push to create {}", branch.push_remote())
+            }
+            (BranchAction::Rebase, Some(upstream), _) => format!("Rebase onto {}", upstream.name),
+            (BranchAction::Merge, Some(upstream), _) => {
+                format!("Merge {} into it", upstream.name)
+            }
+            (BranchAction::DeleteMerged, _, Some(target)) => {
+                format!("Delete (already merged into {target})")
+            }
+            _ => self.description().to_string(),
+        }
+    }
 }
 
-enum ActionResult {
+pub enum ActionResult {
     Handled,
     NotHandled,
     ExitToShell(PathBuf),
 }
 
+/// How far [`GitCleaner`] got with a branch.
+enum Step {
+    NothingToDo,
+    /// Left alone, with why it needed action.
+    Declined(String),
+    /// The action with this label was carried out.
+    Performed(String, ActionResult),
+}
+
+/// What became of a branch in an unattended run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "outcome", content = "detail", rename_all = "snake_case")]
+pub enum BranchOutcome {
+    /// The action with this label was carried out.
+    Done(String),
+    /// Left for someone to deal with, and why.
+    NeedsHuman(String),
+    Failed(String),
+}
+
+impl BranchOutcome {
+    pub fn detail(&self) -> &str {
+        match self {
+            Self::Done(detail) | Self::NeedsHuman(detail) | Self::Failed(detail) => detail,
+        }
+    }
+}
+
+/// What the cleaner suggests doing with a branch.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Proposal {
+    Nothing,
+    /// Safe enough to do without asking.
+    Automatic(BranchAction),
+    /// Ask, offering `actions` with the first one as the default.
+    Choice {
+        message: String,
+        actions: Vec<BranchAction>,
+    },
+}
+
+impl Proposal {
+    /// The action a non-interactive run would take.
+    pub fn default_action(&self) -> Option<BranchAction> {
+        match self {
+            Proposal::Nothing => None,
+            Proposal::Automatic(action) => Some(*action),
+            Proposal::Choice { actions, .. } => actions.first().copied(),
+        }
+    }
+
+    /// Moves `action` to the front of the menu, if it is in it.
+    fn preferring(self, action: BranchAction) -> Self {
+        match self {
+            Proposal::Choice { message, actions } if actions.contains(&action) => {
+                Proposal::Choice {
+                    message,
+                    actions: std::iter::once(action)
+                        .chain(actions.into_iter().filter(|other| *other != action))
+                        .collect(),
+                }
+            }
+            proposal => proposal,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Proposal::Nothing => "Nothing to do".to_string(),
+            Proposal::Automatic(action) => action.description().to_string(),
+            Proposal::Choice { message, .. } => message.clone(),
+        }
+    }
+}
+
+/// What to do with `branch`, offering the configured default action for its status
+/// first when it is one of the options. What its pull request says comes after that,
+/// since it knows more about this particular branch.
+pub fn propose(repo: &GitRepo, branch: &Branch, default_actions: &DefaultActions) -> Proposal {
+    let proposal = propose_for_status(repo, branch);
+    let proposal = match default_actions.get(&branch_status(branch)) {
+        Some(action) => proposal.preferring(*action),
+        None => proposal,
+    };
+    with_pull_request(proposal, branch)
+}
+
+/// A merged pull request means the branch can go, even when git can't tell because it
+/// was squashed or rebased, as long as the branch hasn't moved on since. Otherwise the
+/// branch isn't deleted by default: an open pull request is still being worked on and
+/// doesn't need another one, and a closed one was turned down, which is for the user to
+/// weigh. Either way it can be opened in the browser.
+fn with_pull_request(proposal: Proposal, branch: &Branch) -> Proposal {
+    let (Some(pull), Proposal::Choice { message, actions }) = (&branch.pull_request, &proposal)
+    else {
+        return proposal;
+    };
+    let mut actions: Vec<BranchAction> = actions
+        .iter()
+        .copied()
+        .filter(|action| !(pull.state.is_active() && action.creates_pull_request()))
+        .collect();
+    let open_at = actions
+        .iter()
+        .position(|action| *action == BranchAction::Log)
+        .unwrap_or(actions.len().saturating_sub(1));
+    actions.insert(open_at, BranchAction::OpenPr);
+    let delete = [
+        BranchAction::DeleteWorktreeAndBranch,
+        BranchAction::DeleteMerged,
+        BranchAction::Delete,
+    ]
+    .into_iter()
+    .find(|delete| actions.contains(delete));
+    let proposal = Proposal::Choice {
+        message: format!("{message} (pull request {})", pull.summary()),
+        actions,
+    };
+    match pull.state {
+        PullRequestState::Merged if merged_whole_branch(pull, branch) => match delete {
+            Some(delete) => proposal.preferring(delete),
+            None => proposal,
+        },
+        PullRequestState::Closed => proposal.preferring(BranchAction::Nothing),
+        _ if proposal
+            .default_action()
+            .is_some_and(|action| action.is_destructive_for(branch)) =>
+        {
+            proposal.preferring(BranchAction::Nothing)
+        }
+        _ => proposal,
+    }
+}
+
+/// Whether `pull` took in everything on `branch`: the branch is still at the commit the
+/// pull request was merged from or, for forges that don't tell which commit that was,
+/// its upstream was deleted after the merge.
+fn merged_whole_branch(pull: &PullRequest, branch: &Branch) -> bool {
+    match &pull.head_sha {
+        Some(head_sha) => *head_sha == branch.tip,
+        None => branch
+            .upstream
+            .as_ref()
+            .is_some_and(|upstream| upstream.status == UpstreamStatus::UpstreamIsGone),
+    }
+}
+
+fn propose_for_status(repo: &GitRepo, branch: &Branch) -> Proposal {
+    let Some(upstream) = &branch.upstream else {
+        let mut message = "Branch has no upstream".to_string();
+        let mut actions = vec![
+            BranchAction::CreatePr,
+            BranchAction::PushCreatingOrigin,
+            BranchAction::Delete,
+            BranchAction::Log,
+            BranchAction::Shell,
+            BranchAction::Nothing,
+        ];
+        if let Some(parent) = repo.stack_parent(&branch.refname) {
+            message = format!("{message} (stacked on {parent})");
+            actions.insert(1, BranchAction::CreateStackedPr);
+        }
+        return choice(branch, message, &actions);
+    };
+    match upstream.status {
+        UpstreamStatus::Identical => Proposal::Nothing,
+        UpstreamStatus::UpstreamIsAheadOfLocal => Proposal::Automatic(BranchAction::Rebase),
+        UpstreamStatus::LocalIsAheadOfUpstream => match &branch.push_target {
+            Some(push_target) => propose_for_push_target(branch, push_target),
+            None => choice(
+                branch,
+                format!(
+                    "Branch is {} ahead of {}",
+                    commits(upstream.ahead),
+                    upstream.name
+                ),
+                &[
+                    BranchAction::Push,
+                    BranchAction::Log,
+                    BranchAction::Shell,
+                    BranchAction::Nothing,
+                ],
+            ),
+        },
+        UpstreamStatus::MergeNeeded => choice(
+            branch,
+            format!(
+                "Different commits on local and upstream ({} ahead, {} behind {})",
+                upstream.ahead, upstream.behind, upstream.name
+            ),
+            &[
+                sync_action(repo),
+                BranchAction::Log,
+                BranchAction::Delete,
+                BranchAction::Shell,
+                BranchAction::Nothing,
+            ],
+        ),
+        UpstreamStatus::UpstreamIsGone => {
+            let mut actions = vec![
+                BranchAction::Delete,
+                BranchAction::Log,
+                BranchAction::Shell,
+                BranchAction::Nothing,
+            ];
+            if branch_checked_out_elsewhere(branch, repo) {
+                actions.insert(0, BranchAction::DeleteWorktreeAndBranch);
+            }
+            choice(
+                branch,
+                "Upstream is set, but it is gone".to_string(),
+                &actions,
+            )
+        }
+    }
+}
+
+/// In a triangular workflow the branch is naturally ahead of the upstream it pulls
+/// from, so what matters is how it compares to where it gets pushed.
+fn propose_for_push_target(branch: &Branch, push_target: &Upstream) -> Proposal {
+    let message = match push_target.status {
+        UpstreamStatus::Identical => return Proposal::Nothing,
+        UpstreamStatus::UpstreamIsGone => {
+            format!("Branch has not been pushed to {}", push_target.remote)
+        }
+        UpstreamStatus::LocalIsAheadOfUpstream => format!(
+            "Branch is {} ahead of {}",
+            commits(push_target.ahead),
+            push_target.name
+        ),
+        UpstreamStatus::UpstreamIsAheadOfLocal | UpstreamStatus::MergeNeeded => {
+            return choice(
+                branch,
+                format!(
+                    "Different commits on local and {} ({} ahead, {} behind)",
+                    push_target.name, push_target.ahead, push_target.behind
+                ),
+                // Only showing what diverged, so leaving it alone comes first.
+                &[
+                    BranchAction::Nothing,
+                    BranchAction::Log,
+                    BranchAction::Shell,
+                ],
+            );
+        }
+    };
+    choice(
+        branch,
+        message,
+        &[
+            BranchAction::Push,
+            BranchAction::Log,
+            BranchAction::Shell,
+            BranchAction::Nothing,
+        ],
+    )
+}
+
+fn choice(branch: &Branch, message: String, actions: &[BranchAction]) -> Proposal {
+    let actions = with_merged_delete_first(branch, actions);
+    if !branch.protected {
+        return Proposal::Choice { message, actions };
+    }
+    let kept: Vec<BranchAction> = actions
+        .iter()
+        .copied()
+        .filter(|action| !action.is_destructive_for(branch))
+        .collect();
+    let filtered_out = kept.len() < actions.len();
+    // Rebase is only kept when it is a fast-forward.
+    let safe_default = if kept.contains(&BranchAction::Rebase) {
+        BranchAction::Rebase
+    } else {
+        BranchAction::Nothing
+    };
+    let proposal = Proposal::Choice {
+        message: format!("{message} (protected)"),
+        actions: kept,
+    };
+    // Whatever followed the destructive default isn't meant as one, so fall back
+    // to leaving the branch alone.
+    if filtered_out {
+        proposal.preferring(safe_default)
+    } else {
+        proposal
+    }
+}
+
+/// How to bring a diverged branch up to date, per the repository's
+/// `branch-assistant.syncStrategy`.
+fn sync_action(repo: &GitRepo) -> BranchAction {
+    match repo.config().sync_strategy {
+        SyncStrategy::Rebase => BranchAction::Rebase,
+        SyncStrategy::Merge => BranchAction::Merge,
+    }
+}
+
+fn commits(count: u32) -> String {
+    if count == 1 {
+        "1 commit".to_string()
+    } else {
+        format!("{count} commits")
+    }
+}
+
+/// For a branch whose changes are already in the default branch, deleting it is the
+/// natural thing to do, so it replaces the plain delete option and becomes the default.
+fn with_merged_delete_first(branch: &Branch, actions: &[BranchAction]) -> Vec<BranchAction> {
+    if branch.merged_into.is_none() {
+        return actions.to_vec();
+    }
+    std::iter::once(BranchAction::DeleteMerged)
+        .chain(
+            actions
+                .iter()
+                .copied()
+                .filter(|action| *action != BranchAction::Delete),
+        )
+        .collect()
+}
+
 fn worktree_elsewhere_path(branch: &Branch, repo: &GitRepo) -> Option<PathBuf> {
     if branch_checked_out_elsewhere(branch, repo) {
         branch.worktree_path.clone()
@@ -310,10 +814,6 @@ fn branch_checked_out_elsewhere(branch: &Branch, repo: &GitRepo) -> bool {
     }
 }
 
-fn print_worktree_hint(branch: &Branch, path: &Path) {
-    println!("{}", worktree_location_message(branch, path));
-}
-
 fn print_worktree_redirect(branch: &Branch, path: &Path) {
     eprintln!("{}", worktree_location_message(branch, path));
 }
@@ -334,13 +834,40 @@ fn paths_equivalent(a: &Path, b: &Path) -> bool {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use crate::forge::ReviewStatus;
+    use crate::git::backend::{FakeBackend, RecordingBackend};
     use crate::git::{Branch, Upstream, UpstreamStatus};
+    use crate::journal::JournalOutcome;
+    use crate::services::git_repos_list_service::BranchStatus;
+    use crate::ui::{AssumedChoice, DryRunPrompt};
     use anyhow::{Result, anyhow};
     use std::sync::{Arc, Mutex};
     use tempfile::tempdir;
 
+    /// A branch with no worktree, merge or pull request, tracking `origin/<name>` with
+    /// `status` if it has an upstream.
+    pub(crate) fn branch(name: &str, status: Option<UpstreamStatus>) -> Branch {
+        Branch {
+            refname: name.into(),
+            tip: "0000000".into(),
+            upstream: status.map(|status| Upstream {
+                name: format!("origin/{name}"),
+                remote: "origin".into(),
+                status,
+                ahead: 0,
+                behind: 0,
+            }),
+            worktree_path: None,
+            merged_into: None,
+            push_remote: None,
+            push_target: None,
+            protected: false,
+            pull_request: None,
+        }
+    }
+
     #[derive(Clone, Default)]
     struct TestPrompt {
         selections: Arc<Mutex<Vec<usize>>>,
@@ -372,14 +899,7 @@ mod tests {
     fn local_ahead_branch_allows_do_nothing() -> Result<()> {
         let temp = tempdir()?;
         let repo = GitRepo::new(temp.path().to_path_buf());
-        let branch = Branch {
-            refname: "feature".into(),
-            upstream: Some(Upstream {
-                name: "origin/feature".into(),
-                status: UpstreamStatus::LocalIsAheadOfUpstream,
-            }),
-            worktree_path: None,
-        };
+        let branch = branch("feature", Some(UpstreamStatus::LocalIsAheadOfUpstream));
 
         let cleaner = GitCleaner::new(TestPrompt::with_selections(vec![3]));
         let result = cleaner.handle_branch(&repo, &branch)?;
@@ -391,14 +911,7 @@ mod tests {
     fn identical_branch_skips_prompt() -> Result<()> {
         let temp = tempdir()?;
         let repo = GitRepo::new(temp.path().to_path_buf());
-        let branch = Branch {
-            refname: "feature".into(),
-            upstream: Some(Upstream {
-                name: "origin/feature".into(),
-                status: UpstreamStatus::Identical,
-            }),
-            worktree_path: None,
-        };
+        let branch = branch("feature", Some(UpstreamStatus::Identical));
 
         let cleaner = GitCleaner::new(TestPrompt::default());
         let result = cleaner.handle_branch(&repo, &branch)?;
@@ -412,12 +925,8 @@ mod tests {
         let temp_worktree = tempdir()?;
         let repo = GitRepo::new(temp_repo.path().to_path_buf());
         let branch = Branch {
-            refname: "feature".into(),
-            upstream: Some(Upstream {
-                name: "origin/feature".into(),
-                status: UpstreamStatus::UpstreamIsAheadOfLocal,
-            }),
             worktree_path: Some(temp_worktree.path().to_path_buf()),
+            ..branch("feature", Some(UpstreamStatus::UpstreamIsAheadOfLocal))
         };
 
         let cleaner = GitCleaner::new(TestPrompt::default());
@@ -437,12 +946,8 @@ mod tests {
         let temp_worktree = tempdir()?;
         let repo = GitRepo::new(temp_repo.path().to_path_buf());
         let branch = Branch {
-            refname: "feature".into(),
-            upstream: Some(Upstream {
-                name: "origin/feature".into(),
-                status: UpstreamStatus::LocalIsAheadOfUpstream,
-            }),
             worktree_path: Some(temp_worktree.path().to_path_buf()),
+            ..branch("feature", Some(UpstreamStatus::LocalIsAheadOfUpstream))
         };
 
         let cleaner = GitCleaner::new(TestPrompt::with_selections(vec![2]));
@@ -461,9 +966,8 @@ mod tests {
         let temp = tempdir()?;
         let repo = GitRepo::new(temp.path().to_path_buf());
         let branch = Branch {
-            refname: "feature".into(),
-            upstream: None,
             worktree_path: Some(temp.path().to_path_buf()),
+            ..branch("feature", None)
         };
 
         assert!(!branch_checked_out_elsewhere(&branch, &repo));
@@ -476,12 +980,374 @@ mod tests {
         let temp_worktree = tempdir()?;
         let repo = GitRepo::new(temp_repo.path().to_path_buf());
         let branch = Branch {
-            refname: "feature".into(),
-            upstream: None,
             worktree_path: Some(temp_worktree.path().to_path_buf()),
+            ..branch("feature", None)
         };
 
         assert!(branch_checked_out_elsewhere(&branch, &repo));
         Ok(())
     }
+
+    #[test]
+    fn merged_branch_offers_merged_delete_first() {
+        let branch = Branch {
+            merged_into: Some("main".into()),
+            ..branch("feature", None)
+        };
+        let actions = with_merged_delete_first(
+            &branch,
+            &[
+                BranchAction::CreatePr,
+                BranchAction::Delete,
+                BranchAction::Nothing,
+            ],
+        );
+        assert_eq!(
+            actions,
+            vec![
+                BranchAction::DeleteMerged,
+                BranchAction::CreatePr,
+                BranchAction::Nothing,
+            ]
+        );
+        assert_eq!(
+            actions[0].label(&branch),
+            "Delete (already merged into main)"
+        );
+    }
+
+    fn fake_repo() -> (Arc<FakeBackend>, GitRepo) {
+        let fake = Arc::new(FakeBackend::new());
+        let repo = GitRepo::with_backend(PathBuf::from("/tmp/repo"), fake.clone());
+        (fake, repo)
+    }
+
+    #[test]
+    fn push_goes_to_branch_push_remote() -> Result<()> {
+        let (fake, repo) = fake_repo();
+        let branch = Branch {
+            upstream: Some(Upstream {
+                name: "fork/feature".into(),
+                remote: "fork".into(),
+                status: UpstreamStatus::LocalIsAheadOfUpstream,
+                ahead: 2,
+                behind: 0,
+            }),
+            push_remote: Some("fork".into()),
+            ..branch("feature", None)
+        };
+
+        let cleaner = GitCleaner::new(TestPrompt::with_selections(vec![0]));
+        let result = cleaner.handle_branch(&repo, &branch)?;
+        assert!(matches!(result, TaskResult::Proceed));
+        assert_eq!(fake.invocations(), vec!["git push fork feature"]);
+        Ok(())
+    }
+
+    #[test]
+    fn deleting_merged_branch_checks_out_default_branch_first() -> Result<()> {
+        let (fake, repo) = fake_repo();
+        fake.respond("git remote", "origin\n").respond(
+            "git symbolic-ref --short refs/remotes/origin/HEAD",
+            "origin/main\n",
+        );
+        let branch = Branch {
+            merged_into: Some("main".into()),
+            ..branch("feature", None)
+        };
+
+        let cleaner = GitCleaner::new(TestPrompt::with_selections(vec![0]));
+        cleaner.handle_branch(&repo, &branch)?;
+        let writes: Vec<String> = fake
+            .invocations()
+            .into_iter()
+            .filter(|command| {
+                !command.starts_with("git remote")
+                    && !command.starts_with("git symbolic-ref")
+                    && !command.starts_with("git rev-parse")
+            })
+            .collect();
+        assert_eq!(writes.len(), 3);
+        assert!(writes[0].starts_with("git update-ref refs/branch-assistant/backup/feature/"));
+        assert_eq!(writes[1..], ["git checkout main", "git branch -D feature"]);
+        Ok(())
+    }
+
+    #[test]
+    fn journal_records_actions_that_ran_commands() -> Result<()> {
+        let temp = tempdir()?;
+        let journal = Journal::new(temp.path().join("journal.jsonl"));
+        let (fake, repo) = fake_repo();
+        fake.respond("git rev-parse", "1111111\n")
+            .fail("git push origin broken");
+
+        let cleaner = GitCleaner::new(TestPrompt::default()).with_journal(Some(journal.clone()));
+        cleaner.perform_action(&repo, &branch("feature", None), BranchAction::Push)?;
+        cleaner.perform_action(&repo, &branch("feature", None), BranchAction::Nothing)?;
+        assert!(
+            cleaner
+                .perform_action(&repo, &branch("broken", None), BranchAction::Push)
+                .is_err()
+        );
+
+        let entries = journal.read()?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].action, "Push to origin");
+        assert_eq!(entries[0].commands, vec!["git push origin feature"]);
+        assert_eq!(entries[0].old_tip.as_deref(), Some("0000000"));
+        assert_eq!(entries[0].new_tip.as_deref(), Some("1111111"));
+        assert_eq!(entries[0].outcome, JournalOutcome::Succeeded);
+        assert_eq!(entries[1].branch, "broken");
+        assert!(matches!(entries[1].outcome, JournalOutcome::Failed(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn protected_branch_is_never_offered_delete_or_rewrite() -> Result<()> {
+        let (fake, repo) = fake_repo();
+        let main = |status| Branch {
+            protected: true,
+            ..branch("main", Some(status))
+        };
+        assert_eq!(
+            propose(
+                &repo,
+                &main(UpstreamStatus::UpstreamIsGone),
+                &DefaultActions::new()
+            ),
+            Proposal::Choice {
+                message: "Upstream is set, but it is gone (protected)".into(),
+                actions: vec![
+                    BranchAction::Nothing,
+                    BranchAction::Log,
+                    BranchAction::Shell,
+                ],
+            }
+        );
+
+        assert_eq!(
+            propose(
+                &repo,
+                &main(UpstreamStatus::MergeNeeded),
+                &DefaultActions::new()
+            )
+            .default_action(),
+            Some(BranchAction::Nothing)
+        );
+
+        let main = main(UpstreamStatus::UpstreamIsAheadOfLocal);
+        assert_eq!(
+            propose(&repo, &main, &DefaultActions::new()),
+            Proposal::Automatic(BranchAction::Rebase)
+        );
+
+        let cleaner = GitCleaner::new(TestPrompt::default());
+        assert!(
+            cleaner
+                .perform_action(&repo, &main, BranchAction::Delete)
+                .is_err()
+        );
+        assert!(
+            fake.invocations()
+                .iter()
+                .all(|command| command.starts_with("git config"))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn dry_run_settles_diverged_protected_branch_without_running_anything() -> Result<()> {
+        let fake = Arc::new(FakeBackend::new());
+        let recording = Arc::new(RecordingBackend::new(fake.clone()).quiet());
+        let repo = GitRepo::with_backend(PathBuf::from("/tmp/repo"), recording.clone());
+        let main = Branch {
+            protected: true,
+            ..branch("main", Some(UpstreamStatus::MergeNeeded))
+        };
+
+        for assumed_choice in [AssumedChoice::Default, AssumedChoice::Nothing] {
+            let cleaner = GitCleaner::new(DryRunPrompt::new(assumed_choice).quiet());
+            let result = cleaner.handle_branch(&repo, &main)?;
+            assert!(matches!(result, TaskResult::Proceed));
+        }
+        assert!(recording.commands().is_empty());
+        assert!(
+            fake.invocations()
+                .iter()
+                .all(|command| command.starts_with("git config"))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn diverged_push_target_defaults_to_nothing() {
+        let (_fake, repo) = fake_repo();
+        let topic = Branch {
+            push_remote: Some("fork".into()),
+            push_target: Some(Upstream {
+                name: "fork/topic".into(),
+                remote: "fork".into(),
+                status: UpstreamStatus::MergeNeeded,
+                ahead: 1,
+                behind: 2,
+            }),
+            ..branch("topic", Some(UpstreamStatus::LocalIsAheadOfUpstream))
+        };
+        assert_eq!(
+            propose(&repo, &topic, &DefaultActions::new()).default_action(),
+            Some(BranchAction::Nothing)
+        );
+    }
+
+    #[test]
+    fn merge_sync_strategy_offers_merge_for_diverged_branch() -> Result<()> {
+        let (fake, repo) = fake_repo();
+        fake.respond(
+            "git config --get-regexp",
+            "branch-assistant.syncstrategy merge",
+        );
+        let branch = branch("feature", Some(UpstreamStatus::MergeNeeded));
+        let proposal = propose(&repo, &branch, &DefaultActions::new());
+        assert_eq!(proposal.default_action(), Some(BranchAction::Merge));
+
+        GitCleaner::new(TestPrompt::default()).perform_action(
+            &repo,
+            &branch,
+            BranchAction::Merge,
+        )?;
+        assert!(
+            fake.invocations()
+                .contains(&"git merge --no-edit origin/feature".to_string())
+        );
+        Ok(())
+    }
+
+    fn pull(state: PullRequestState, head_sha: Option<&str>) -> Option<PullRequest> {
+        Some(PullRequest {
+            number: 12,
+            state,
+            url: "https://github.com/o/r/pull/12".into(),
+            review: Some(ReviewStatus::Approved),
+            head_sha: head_sha.map(Into::into),
+        })
+    }
+
+    #[test]
+    fn pull_request_state_chooses_default_action() {
+        let (_, repo) = fake_repo();
+        let mut gone = Branch {
+            pull_request: pull(PullRequestState::Open, None),
+            ..branch("feature", Some(UpstreamStatus::UpstreamIsGone))
+        };
+        let proposal = propose(&repo, &gone, &DefaultActions::new());
+        assert_eq!(proposal.default_action(), Some(BranchAction::Nothing));
+        assert_eq!(
+            proposal.message(),
+            "Upstream is set, but it is gone (pull request #12 open, approved)"
+        );
+
+        gone.pull_request = pull(PullRequestState::Merged, None);
+        let proposal = propose(&repo, &gone, &DefaultActions::new());
+        assert_eq!(proposal.default_action(), Some(BranchAction::Delete));
+
+        let mut local = Branch {
+            upstream: None,
+            pull_request: pull(PullRequestState::Merged, Some("0000000")),
+            ..gone
+        };
+        let proposal = propose(&repo, &local, &DefaultActions::new());
+        assert_eq!(proposal.default_action(), Some(BranchAction::Delete));
+
+        local.pull_request = pull(PullRequestState::Merged, None);
+        let proposal = propose(&repo, &local, &DefaultActions::new());
+        assert_ne!(proposal.default_action(), Some(BranchAction::Delete));
+
+        local.pull_request = pull(PullRequestState::Draft, None);
+        let Proposal::Choice { actions, .. } = propose(&repo, &local, &DefaultActions::new())
+        else {
+            panic!("expected a choice");
+        };
+        assert_eq!(actions[0], BranchAction::PushCreatingOrigin);
+        assert!(!actions.contains(&BranchAction::CreatePr));
+        assert!(actions.contains(&BranchAction::OpenPr));
+    }
+
+    #[test]
+    fn merged_pull_request_prefers_the_delete_on_offer() {
+        let (_, repo) = fake_repo();
+        let gone = Branch {
+            pull_request: pull(PullRequestState::Merged, Some("0000000")),
+            worktree_path: Some(PathBuf::from("/tmp/elsewhere")),
+            ..branch("feature", Some(UpstreamStatus::UpstreamIsGone))
+        };
+        let proposal = propose(&repo, &gone, &DefaultActions::new());
+        assert_eq!(
+            proposal.default_action(),
+            Some(BranchAction::DeleteWorktreeAndBranch)
+        );
+
+        let merged = Branch {
+            worktree_path: None,
+            merged_into: Some("main".into()),
+            ..gone
+        };
+        let proposal = propose(&repo, &merged, &DefaultActions::new());
+        assert_eq!(proposal.default_action(), Some(BranchAction::DeleteMerged));
+    }
+
+    #[test]
+    fn closed_or_outgrown_pull_request_does_not_default_to_delete() {
+        let (_, repo) = fake_repo();
+        let gone = Branch {
+            pull_request: pull(PullRequestState::Closed, None),
+            ..branch("feature", Some(UpstreamStatus::UpstreamIsGone))
+        };
+        let proposal = propose(&repo, &gone, &DefaultActions::new());
+        assert_eq!(proposal.default_action(), Some(BranchAction::Nothing));
+
+        let diverged = Branch {
+            pull_request: pull(PullRequestState::Merged, Some("1111111")),
+            ..branch("feature", Some(UpstreamStatus::MergeNeeded))
+        };
+        let proposal = propose(&repo, &diverged, &DefaultActions::new());
+        assert_eq!(proposal.default_action(), Some(BranchAction::Nothing));
+
+        let moved_on = Branch {
+            pull_request: pull(PullRequestState::Merged, Some("1111111")),
+            ..gone
+        };
+        let proposal = propose(&repo, &moved_on, &DefaultActions::new());
+        assert_eq!(proposal.default_action(), Some(BranchAction::Nothing));
+    }
+
+    #[test]
+    fn custom_actions_are_offered_for_their_statuses() -> Result<()> {
+        let (fake, repo) = fake_repo();
+        let branch = branch("it's", Some(UpstreamStatus::LocalIsAheadOfUpstream));
+        let custom = |label: &str, statuses| CustomAction {
+            label: label.into(),
+            statuses,
+            command: "make test BRANCH={branch} IN={worktree} UP={upstream}".into(),
+            handled: false,
+        };
+        let cleaner =
+            GitCleaner::new(TestPrompt::with_selections(vec![3, 4])).with_custom_actions(vec![
+                custom("Run tests", vec![BranchStatus::LocalAhead]),
+                custom("Archive", vec![BranchStatus::UpstreamGone]),
+            ]);
+
+        let labels: Vec<String> = cleaner
+            .menu(&branch, &[BranchAction::Push, BranchAction::Nothing])
+            .iter()
+            .map(|item| item.label(&branch))
+            .collect();
+        assert_eq!(labels, ["Push to origin", "Run tests", "Do nothing"]);
+
+        cleaner.handle_branch(&repo, &branch)?;
+        assert_eq!(
+            fake.invocations(),
+            ["sh -c make test BRANCH='it'\\''s' IN='/tmp/repo' UP='origin/it'\\''s'"]
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,50 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::cleaner::GitCleaner;
+use crate::git::backend::{ProcessBackend, RecordingBackend, SharedBackend};
+use crate::journal::Journal;
+use crate::plan::{Outcome, Plan};
+use crate::settings::Settings;
+use crate::ui::NoPrompt;
+
+/// Carries out a plan written by `clean --plan` or `repos --plan`. Returns the exit
+/// code: non-zero if any action failed.
+pub fn run(plan_path: &Path, dry: bool, settings: &Settings) -> Result<i32> {
+    let plan = Plan::read(plan_path)?;
+    let backend: SharedBackend = if dry {
+        Arc::new(RecordingBackend::new(ProcessBackend::shared()))
+    } else {
+        ProcessBackend::shared()
+    };
+
+    let cleaner = if dry {
+        GitCleaner::new(NoPrompt)
+    } else {
+        GitCleaner::new(NoPrompt).with_journal(Journal::open_default())
+    };
+
+    let (mut applied, mut skipped, mut failed) = (0, 0, 0);
+    for (entry, outcome) in plan.apply(&cleaner, &backend, settings) {
+        let location = format!("{}:{}", entry.repo.display(), entry.branch);
+        match outcome {
+            Outcome::Applied => {
+                applied += 1;
+                println!("{location}: {}", entry.action.description());
+            }
+            Outcome::Skipped(reason) => {
+                skipped += 1;
+                println!("{location}: skipped, {reason}");
+            }
+            Outcome::Failed(error) => {
+                failed += 1;
+                eprintln!("{location}: failed: {error}");
+            }
+        }
+    }
+    println!("{applied} applied, {skipped} skipped, {failed} failed");
+
+    Ok(if failed > 0 { 1 } else { 0 })
+}
@@ -0,0 +1,15 @@
+use anyhow::Result;
+
+use crate::config::{Config, default_config_path};
+
+/// Prints where the config file is read from and the configuration in effect,
+/// defaults included.
+pub fn show(config: &Config) -> Result<()> {
+    match default_config_path() {
+        Some(path) if path.exists() => println!("# {}", path.display()),
+        Some(path) => println!("# {} (not found, using defaults)", path.display()),
+        None => println!("# no config file location, using defaults"),
+    }
+    print!("{}", config.to_toml()?);
+    Ok(())
+}
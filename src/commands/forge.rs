@@ -0,0 +1,46 @@
+use std::env;
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow};
+
+use crate::git::backend::ProcessBackend;
+use crate::settings::Settings;
+
+/// Shows which forge a repository is hosted on, its web page, and the pull request
+/// for a branch (the checked out one by default).
+pub fn run(path: Option<PathBuf>, branch: Option<String>, settings: &Settings) -> Result<()> {
+    let repo_path = path
+        .map(Ok)
+        .unwrap_or_else(env::current_dir)?
+        .canonicalize()?;
+    let repo = settings.repo(repo_path, ProcessBackend::shared());
+    let forge = repo.forge();
+    println!("Forge: {}", forge.kind().name());
+    match forge.web_url(&repo) {
+        Ok(url) => println!("URL: {url}"),
+        Err(error) => println!("URL: unknown ({error:#})"),
+    }
+
+    let branch = match branch {
+        Some(branch) => branch,
+        None => repo
+            .current_branch()?
+            .ok_or_else(|| anyhow!("no branch is checked out; name one"))?,
+    };
+    let branches = repo.get_branches()?;
+    let (remote, name) = branches
+        .iter()
+        .find(|candidate| candidate.refname == branch)
+        .ok_or_else(|| anyhow!("no branch named {branch}"))?
+        .remote_branch();
+    match repo.pull_request_for_branch(remote, name)? {
+        Some(pull) => println!(
+            "Pull request for {branch}: #{} ({}) {}",
+            pull.number,
+            pull.state.label(),
+            pull.url
+        ),
+        None => println!("No pull request for {branch}"),
+    }
+    Ok(())
+}
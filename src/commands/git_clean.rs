@@ -1,35 +1,131 @@
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use anyhow::Result;
 
+use crate::auto::{AutoPrompt, AutoReport};
 use crate::cleaner::GitCleaner;
-use crate::git::GitRepo;
+use crate::git::backend::{ProcessBackend, RecordingBackend, SharedBackend};
+use crate::journal::Journal;
+use crate::output::{ActionRecord, OutputFormat, OutputWriter, Record};
+use crate::plan::Plan;
 use crate::repository::Repository;
+use crate::services::git_repos_list_service::branch_status;
+use crate::settings::Settings;
 use crate::task_result::TaskResult;
-use crate::ui::{DialoguerPrompt, DryRunPrompt};
+use crate::ui::{AssumedChoice, DialoguerPrompt, DryRunPrompt};
 
-pub fn run(path: Option<PathBuf>, dry: bool) -> Result<()> {
+/// Writes the actions `run` would propose to a plan file.
+pub fn plan(path: Option<PathBuf>, settings: &Settings, plan_path: &Path) -> Result<()> {
+    let repo_path = path
+        .map(Ok)
+        .unwrap_or_else(env::current_dir)?
+        .canonicalize()?;
+    let repo = settings.repo(repo_path, ProcessBackend::shared());
+    let branches = settings.read_branches(&repo)?;
+
+    let mut plan = Plan::new();
+    plan.add_branches(&repo, &branches, &settings.default_actions);
+    plan.write(plan_path)?;
+    println!(
+        "Wrote {} proposed action(s) to {}",
+        plan.entries.len(),
+        plan_path.display()
+    );
+    Ok(())
+}
+
+/// Deals with the branches by the config file's `auto` rules, without asking, and
+/// reports what became of them. Returns the exit code: non-zero if any action failed.
+pub fn auto(path: Option<PathBuf>, dry: bool, settings: &Settings) -> Result<i32> {
     let repo_path = path.map(Ok).unwrap_or_else(env::current_dir)?;
-    let repo = GitRepo::new(repo_path);
-    let branches = repo.get_branches()?;
+    let backend: SharedBackend = if dry {
+        Arc::new(RecordingBackend::new(ProcessBackend::shared()))
+    } else {
+        ProcessBackend::shared()
+    };
+    let repo = settings.repo(repo_path, backend);
+    let branches = settings.read_branches(&repo)?;
+
+    let cleaner = GitCleaner::new(AutoPrompt::new(settings.auto_rules.clone()))
+        .with_journal(if dry { None } else { Journal::open_default() })
+        .with_default_actions(settings.default_actions.clone())
+        .with_custom_actions(settings.custom_actions.clone());
+    let mut report = AutoReport::default();
+    report.add(repo.dir(), cleaner.handle_unattended(&repo, branches));
+    report.print();
+    Ok(if report.has_failures() { 1 } else { 0 })
+}
+
+/// Like a dry run, but prints what would become of each branch as JSON records.
+fn preview_records(
+    repo_path: PathBuf,
+    assumed_choice: AssumedChoice,
+    settings: &Settings,
+    mut writer: OutputWriter,
+) -> Result<()> {
+    let recording = Arc::new(RecordingBackend::new(ProcessBackend::shared()).quiet());
+    let repo = settings.repo(repo_path, recording.clone());
+    let branches = settings.read_branches(&repo)?;
+    let cleaner = GitCleaner::new(DryRunPrompt::new(assumed_choice).quiet())
+        .with_default_actions(settings.default_actions.clone())
+        .with_custom_actions(settings.custom_actions.clone());
 
-    let result = if dry {
-        let cleaner = GitCleaner::new_with_dry_run(DryRunPrompt::default(), true);
-        cleaner.handle(&repo, branches)?
+    for branch in branches {
+        let status = branch_status(&branch);
+        let outcomes = cleaner.handle_unattended(&repo, vec![branch]);
+        let commands = recording.take_commands();
+        for (branch, outcome) in outcomes {
+            writer.emit(Record::Action(ActionRecord {
+                repo: repo.dir().to_path_buf(),
+                branch,
+                status,
+                outcome,
+                commands: commands.clone(),
+            }))?;
+        }
+    }
+    writer.finish()
+}
+
+pub fn run(
+    path: Option<PathBuf>,
+    dry: bool,
+    assumed_choice: AssumedChoice,
+    output: OutputFormat,
+    settings: &Settings,
+) -> Result<()> {
+    let repo_path = path.map(Ok).unwrap_or_else(env::current_dir)?;
+    if let Some(writer) = OutputWriter::new(output) {
+        return preview_records(repo_path, assumed_choice, settings, writer);
+    }
+    let backend: SharedBackend = if dry {
+        Arc::new(RecordingBackend::new(ProcessBackend::shared()))
     } else {
-        let cleaner = GitCleaner::new(DialoguerPrompt::default());
-        cleaner.handle(&repo, branches)?
+        ProcessBackend::shared()
     };
+    let repo = settings.repo(repo_path, backend);
+    let branches = settings.read_branches(&repo)?;
+    let default_actions = settings.default_actions.clone();
+    let custom_actions = settings.custom_actions.clone();
+
+    if dry {
+        let cleaner = GitCleaner::new(DryRunPrompt::new(assumed_choice))
+            .with_default_actions(default_actions)
+            .with_custom_actions(custom_actions);
+        return cleaner.preview(&repo, branches);
+    }
 
-    match result {
+    let cleaner = GitCleaner::new(DialoguerPrompt)
+        .with_journal(Journal::open_default())
+        .with_default_actions(default_actions)
+        .with_custom_actions(custom_actions);
+    match cleaner.handle(&repo, branches)? {
         TaskResult::Proceed => Ok(()),
         TaskResult::ShellActionRequired(path) => {
-            if !dry {
-                Repository::new().set_suggested_directory(&path)?;
-                std::process::exit(10);
-            }
-            Ok(())
+            Repository::new().set_suggested_directory(&path)?;
+            std::process::exit(10);
         }
     }
 }
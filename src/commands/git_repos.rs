@@ -1,49 +1,138 @@
-use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use anyhow::Result;
 
+use crate::auto::AutoPrompt;
+use crate::git::backend::{ProcessBackend, RecordingBackend, SharedBackend};
+use crate::output::{OutputFormat, OutputWriter, Record};
 use crate::repository::Repository;
-use crate::services::git_repos_list_service::GitReposListService;
+use crate::services::git_repos_list_service::{GitReposListService, ListView};
 use crate::services::git_repos_service::GitReposService;
+use crate::settings::Settings;
 use crate::task_result::TaskResult;
+use crate::ui::AssumedChoice;
+
+/// Writes the actions `run` would propose across all repos to a plan file.
+pub fn plan(
+    path: Option<PathBuf>,
+    skip_dirty_repos: bool,
+    settings: &Settings,
+    plan_path: &Path,
+) -> Result<()> {
+    let roots = settings.roots(path)?;
+    let service = GitReposService::new(
+        None,
+        skip_dirty_repos,
+        settings.clone(),
+        ProcessBackend::shared(),
+    );
+    let plan = service.plan_all_git_repos(&roots)?;
+    plan.write(plan_path)?;
+    println!(
+        "Wrote {} proposed action(s) to {}",
+        plan.entries.len(),
+        plan_path.display()
+    );
+    Ok(())
+}
+
+/// Prints every branch across the repositories, or lets the user pick one with
+/// `interactive`.
+pub fn list(
+    path: Option<PathBuf>,
+    dry: bool,
+    interactive: bool,
+    output: OutputFormat,
+    view: ListView,
+    settings: &Settings,
+) -> Result<i32> {
+    let roots = settings.roots(path)?;
+    let service = GitReposListService::new(interactive && !dry, settings.clone(), backend(dry))
+        .with_output(output)
+        .with_view(view);
+    let result = service.list_all_branches(&roots)?;
+    finish(result, dry)
+}
+
+/// Deals with the branches across the repositories by the config file's `auto` rules,
+/// without asking, and reports what became of them. Returns the exit code: non-zero if
+/// any action failed.
+pub fn auto(
+    path: Option<PathBuf>,
+    dry: bool,
+    skip_dirty_repos: bool,
+    settings: &Settings,
+) -> Result<i32> {
+    let roots = settings.roots(path)?;
+    let service = GitReposService::new(
+        dry.then_some(AssumedChoice::Default),
+        skip_dirty_repos,
+        settings.clone(),
+        backend(dry),
+    );
+    let report =
+        service.auto_all_git_repos(&roots, AutoPrompt::new(settings.auto_rules.clone()))?;
+    report.print();
+    Ok(if report.has_failures() { 1 } else { 0 })
+}
+
+/// Prints what was found in each repository as JSON records, without acting on it.
+pub fn report(
+    path: Option<PathBuf>,
+    skip_dirty_repos: bool,
+    settings: &Settings,
+    mut writer: OutputWriter,
+) -> Result<i32> {
+    let roots = settings.roots(path)?;
+    let service = GitReposService::new(
+        None,
+        skip_dirty_repos,
+        settings.clone(),
+        ProcessBackend::shared(),
+    );
+    for record in service.report_all_git_repos(&roots)? {
+        writer.emit(Record::Repo(record))?;
+    }
+    writer.finish()?;
+    Ok(0)
+}
 
 pub fn run(
     path: Option<PathBuf>,
     dry: bool,
+    assumed_choice: AssumedChoice,
     skip_dirty_repos: bool,
-    list: bool,
-    interactive: bool,
+    settings: &Settings,
 ) -> Result<i32> {
-    let path = path
-        .map(Ok)
-        .unwrap_or_else(env::current_dir)?
-        .canonicalize()?;
-
-    let result = if list {
-        let service = GitReposListService::new(interactive && !dry);
-        service.list_all_branches(&path)?
+    let roots = settings.roots(path)?;
+    let service = GitReposService::new(
+        dry.then_some(assumed_choice),
+        skip_dirty_repos,
+        settings.clone(),
+        backend(dry),
+    );
+    let result = service.handle_all_git_repos(&roots)?;
+    finish(result, dry)
+}
+
+fn backend(dry: bool) -> SharedBackend {
+    if dry {
+        Arc::new(RecordingBackend::new(ProcessBackend::shared()))
     } else {
-        let service = GitReposService::new(dry, skip_dirty_repos);
-        service.handle_all_git_repos(&path)?
-    };
-
-    let exit_code = match &result {
-        TaskResult::Proceed => 0,
-        TaskResult::ShellActionRequired(_) => {
-            if dry {
-                0
-            } else {
-                10
-            }
-        }
-    };
+        ProcessBackend::shared()
+    }
+}
 
-    if !dry {
-        if let TaskResult::ShellActionRequired(directory) = &result {
-            Repository::new().set_suggested_directory(directory)?;
+/// The exit code for `result`, suggesting the directory to change to if a real run
+/// needs the shell.
+fn finish(result: TaskResult, dry: bool) -> Result<i32> {
+    match result {
+        TaskResult::Proceed => Ok(0),
+        TaskResult::ShellActionRequired(_) if dry => Ok(0),
+        TaskResult::ShellActionRequired(directory) => {
+            Repository::new().set_suggested_directory(&directory)?;
+            Ok(10)
         }
     }
-
-    Ok(exit_code)
 }
@@ -0,0 +1,29 @@
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow};
+
+use crate::journal::{HistoryFilter, Journal, format_entry};
+
+/// Prints the journal of actions performed, newest first.
+pub fn run(filter: HistoryFilter, limit: Option<usize>, verbose: bool) -> Result<()> {
+    let journal = Journal::open_default()
+        .ok_or_else(|| anyhow!("could not determine the state directory"))?;
+    let entries = journal.read()?;
+    let matching = entries
+        .iter()
+        .rev()
+        .filter(|entry| filter.matches(entry))
+        .take(limit.unwrap_or(usize::MAX));
+    for entry in matching {
+        for line in format_entry(entry, verbose) {
+            println!("{line}");
+        }
+    }
+    Ok(())
+}
+
+/// Makes a `--repo` argument absolute so that it can be compared with journal paths.
+pub fn repo_filter(repo: Option<PathBuf>) -> Result<Option<PathBuf>> {
+    repo.map(|repo| repo.canonicalize().map_err(Into::into))
+        .transpose()
+}
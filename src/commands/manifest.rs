@@ -0,0 +1,53 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+
+use crate::discovery::Discovered;
+use crate::git::backend::ProcessBackend;
+use crate::manifest::{Manifest, ManifestRepo};
+use crate::settings::Settings;
+
+/// Writes a manifest of the repositories found by scanning a workspace root, to
+/// `output` or else to stdout.
+pub fn export(path: Option<PathBuf>, output: Option<&Path>, settings: &Settings) -> Result<()> {
+    let roots = settings.roots(path)?;
+    let [root] = roots.as_slice() else {
+        bail!("a manifest describes a single workspace root; pick one with --path");
+    };
+
+    let mut manifest = Manifest::default();
+    for found in settings.discovery.scan(&roots)? {
+        let Discovered::Directory(dir) = found else {
+            continue;
+        };
+        if !dir.join(".git").exists() {
+            continue;
+        }
+        let repo = settings.repo(dir.clone(), ProcessBackend::shared());
+        let Some(url) = repo.remote_url() else {
+            eprintln!("Skipping {}: it has no remote", dir.display());
+            continue;
+        };
+        manifest.repos.push(ManifestRepo {
+            path: dir.strip_prefix(root).unwrap_or(&dir).to_path_buf(),
+            url,
+            default_branch: repo.config().default_branch.clone(),
+        });
+    }
+
+    let toml = manifest.to_toml()?;
+    match output {
+        Some(output) => {
+            fs::write(output, toml)
+                .with_context(|| format!("failed to write manifest {}", output.display()))?;
+            println!(
+                "Wrote {} repositories to {}",
+                manifest.repos.len(),
+                output.display()
+            );
+        }
+        None => print!("{toml}"),
+    }
+    Ok(())
+}
@@ -1,2 +1,9 @@
+pub mod apply;
+pub mod config;
+pub mod forge;
 pub mod git_clean;
 pub mod git_repos;
+pub mod history;
+pub mod manifest;
+pub mod restore;
+pub mod sync;
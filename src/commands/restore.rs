@@ -0,0 +1,89 @@
+use std::env;
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow};
+
+use crate::cache::now_unix;
+use crate::git::{Backup, GitRepo};
+use crate::journal::{Journal, entry_for_backup, format_timestamp, record};
+use crate::ui::{DialoguerPrompt, Prompt};
+
+/// Restores a branch from one of the backups taken before it was deleted or rebased.
+pub fn run(path: Option<PathBuf>, branch: Option<String>, list: bool) -> Result<()> {
+    let repo_path = path
+        .map(Ok)
+        .unwrap_or_else(env::current_dir)?
+        .canonicalize()?;
+    let repo = GitRepo::new(repo_path);
+    let journal = Journal::open_default();
+    let backups = repo.backups()?;
+    if backups.is_empty() {
+        println!("No backups in {}", repo.dir().display());
+        return Ok(());
+    }
+
+    let journal_entries = match &journal {
+        Some(journal) => journal.read()?,
+        None => Vec::new(),
+    };
+    let labels: Vec<String> = backups
+        .iter()
+        .map(|backup| {
+            let action = entry_for_backup(&journal_entries, &backup.refname)
+                .map(|entry| format!("before {}", entry.action))
+                .unwrap_or_default();
+            format!(
+                "{}  {}  {}  {}",
+                format_timestamp(backup.timestamp),
+                &backup.tip[..backup.tip.len().min(7)],
+                backup.branch,
+                action
+            )
+            .trim_end()
+            .to_string()
+        })
+        .collect();
+
+    if list {
+        for label in labels {
+            println!("{label}");
+        }
+        return Ok(());
+    }
+
+    let backup = match branch {
+        Some(branch) => backups
+            .iter()
+            .find(|backup| backup.branch == branch)
+            .ok_or_else(|| anyhow!("no backup of branch {branch}"))?,
+        None => {
+            let index = DialoguerPrompt.select("Restore which backup?", &labels)?;
+            &backups[index]
+        }
+    };
+    restore(&repo, journal.as_ref(), backup)
+}
+
+fn restore(repo: &GitRepo, journal: Option<&Journal>, backup: &Backup) -> Result<()> {
+    let current_tip = repo.branch_tip(&backup.branch)?;
+    if current_tip.as_deref() == Some(backup.tip.as_str()) {
+        println!("Branch {} is already at {}", backup.branch, backup.tip);
+        return Ok(());
+    }
+    record(
+        journal,
+        repo,
+        &backup.branch,
+        format!("Restore from {}", backup.refname),
+        current_tip.clone(),
+        |repo, backup_ref| {
+            if let Some(current_tip) = &current_tip {
+                *backup_ref =
+                    Some(repo.create_backup_ref(&backup.branch, current_tip, now_unix())?);
+            }
+            repo.restore_branch(&backup.branch, &backup.tip)
+        },
+    )?;
+    println!("Restored {} to {}", backup.branch, backup.tip);
+    Ok(())
+}
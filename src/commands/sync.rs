@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{Result, anyhow};
+
+use crate::discovery::Discovered;
+use crate::git::backend::{ProcessBackend, RecordingBackend, SharedBackend};
+use crate::manifest::{MANIFEST_FILE, Manifest};
+use crate::settings::Settings;
+
+/// Clones the repositories in each root's manifest that are missing on disk, and
+/// reports repositories on disk that the manifest doesn't list.
+pub fn run(path: Option<PathBuf>, dry: bool, settings: &Settings) -> Result<i32> {
+    let backend: SharedBackend = if dry {
+        Arc::new(RecordingBackend::new(ProcessBackend::shared()))
+    } else {
+        ProcessBackend::shared()
+    };
+
+    let mut failed = 0;
+    for root in settings.roots(path)? {
+        let manifest = Manifest::find(&root)?.ok_or_else(|| {
+            anyhow!(
+                "no {} in {}; create one with `git-branch-assistant manifest export`",
+                MANIFEST_FILE,
+                root.display()
+            )
+        })?;
+        let root_repo = settings.repo(root.clone(), backend.clone());
+
+        for entry in &manifest.repos {
+            let dir = root.join(&entry.path);
+            if dir.exists() {
+                continue;
+            }
+            println!("Cloning {} into {}", entry.url, dir.display());
+            if let Err(error) = root_repo.clone_repository(&entry.url, &entry.path) {
+                failed += 1;
+                eprintln!("Failed to clone {}: {error:#}", entry.url);
+                continue;
+            }
+            if let Some(default_branch) = &entry.default_branch {
+                let repo = settings.repo(dir.clone(), backend.clone());
+                if let Err(error) =
+                    repo.set_config("branch-assistant.defaultBranch", default_branch)
+                {
+                    failed += 1;
+                    eprintln!(
+                        "Failed to set the default branch of {}: {error:#}",
+                        dir.display()
+                    );
+                    continue;
+                }
+            }
+        }
+
+        for found in settings.discovery.scan(std::slice::from_ref(&root))? {
+            let Discovered::Directory(dir) = found else {
+                continue;
+            };
+            let relative = dir.strip_prefix(&root).unwrap_or(&dir);
+            if dir.join(".git").exists() && !manifest.contains(relative) {
+                println!("Not in manifest: {}", dir.display());
+            }
+        }
+    }
+    Ok(if failed > 0 { 1 } else { 0 })
+}
@@ -0,0 +1,322 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::auto::AutoRules;
+use crate::cleaner::BranchAction;
+use crate::fs_utils::expand_tilde;
+use crate::services::git_repos_list_service::{BranchStatus, Column, GroupBy, SortKey};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Run `git fetch --prune` in every repository before analysing branches.
+    pub fetch: bool,
+    /// Glob patterns for branches that must never be deleted or rewritten.
+    pub protected: Vec<String>,
+    /// Directories `repos` scans when no `--path` is given. Empty means the current
+    /// directory.
+    pub workspace_roots: Vec<String>,
+    /// The remote to push branches to when git doesn't say otherwise.
+    pub remote: String,
+    pub pull_requests: PullRequestsConfig,
+    pub repos: ReposConfig,
+    pub discovery: DiscoveryConfig,
+    pub cache: CacheConfig,
+    pub tools: ToolsConfig,
+    /// The action to offer first for branches in a given status, where it is one of
+    /// the options.
+    pub default_actions: DefaultActions,
+    /// Actions of your own, offered alongside the built-in ones.
+    pub actions: Vec<CustomAction>,
+    /// What `--auto` does with branches in each status.
+    pub auto: AutoRules,
+}
+
+pub type DefaultActions = BTreeMap<BranchStatus, BranchAction>;
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            fetch: false,
+            protected: Vec::new(),
+            workspace_roots: Vec::new(),
+            remote: "origin".to_string(),
+            pull_requests: PullRequestsConfig::default(),
+            repos: ReposConfig::default(),
+            discovery: DiscoveryConfig::default(),
+            cache: CacheConfig::default(),
+            tools: ToolsConfig::default(),
+            default_actions: DefaultActions::new(),
+            actions: Vec::new(),
+            auto: AutoRules::new(),
+        }
+    }
+}
+
+/// A command offered in the menu for branches in the given statuses. `{repo}`,
+/// `{branch}`, `{upstream}` and `{worktree}` in it are replaced by the repository's
+/// path, the branch, its upstream and where it is checked out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomAction {
+    pub label: String,
+    /// The statuses it is offered for; every status if empty.
+    #[serde(default)]
+    pub statuses: Vec<BranchStatus>,
+    /// Run with `sh -c` in the repository.
+    pub command: String,
+    /// Whether running it deals with the branch, or the menu is shown again
+    /// afterwards, as after "Show git log".
+    #[serde(default)]
+    pub handled: bool,
+}
+
+/// Defaults for the flags of the `repos` command.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReposConfig {
+    pub dry: bool,
+    pub skip_dirty_repos: bool,
+    /// What `--list` sorts branches by.
+    pub sort: SortKey,
+    pub reverse: bool,
+    /// What `--list` groups branches under headers by.
+    pub group_by: GroupBy,
+    /// The columns `--list` shows; the usual ones if empty.
+    pub columns: Vec<Column>,
+}
+
+/// How `repos` finds repositories below the workspace roots.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscoveryConfig {
+    /// How many directory levels below a root to look for repositories; 1 means only
+    /// its direct children.
+    pub max_depth: usize,
+    /// Whether to follow symbolic links to directories.
+    pub follow_symlinks: bool,
+    /// Glob patterns for paths to leave out, in addition to each root's ignore file.
+    pub ignore: Vec<String>,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: 1,
+            follow_symlinks: true,
+            ignore: Vec::new(),
+        }
+    }
+}
+
+/// How pull requests are looked up and created.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PullRequestsConfig {
+    /// Look up each branch's pull request on the repository's forge, to show it and to
+    /// choose what to offer first. Off unless asked for, since it runs the forge's tool
+    /// for every repository.
+    pub lookup: bool,
+    /// Open new pull requests as drafts.
+    pub draft: bool,
+    /// Requested to review every new pull request; each repository can add its own.
+    pub reviewers: Vec<String>,
+    /// Added to every new pull request; each repository can add its own.
+    pub labels: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// How long a cached `repos --list` scan, or a repository's cached pull requests,
+    /// are used before looking again.
+    pub ttl_secs: i64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self { ttl_secs: 3600 }
+    }
+}
+
+/// External programs, each given as a command line that arguments are appended to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ToolsConfig {
+    /// Shows the log of a branch.
+    pub log: String,
+    /// The forges' command line tools, used to create and look up pull requests and
+    /// the default branch: GitHub's, GitLab's and Gitea's.
+    pub gh: String,
+    pub glab: String,
+    pub tea: String,
+}
+
+impl Default for ToolsConfig {
+    fn default() -> Self {
+        Self {
+            log: "tig".to_string(),
+            gh: "gh".to_string(),
+            glab: "glab".to_string(),
+            tea: "tea".to_string(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self> {
+        match default_config_path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("failed to read config at {}", path.display()));
+            }
+        };
+        toml::from_str(&content)
+            .with_context(|| format!("failed to parse config at {}", path.display()))
+    }
+
+    pub fn workspace_roots(&self) -> Vec<PathBuf> {
+        self.workspace_roots
+            .iter()
+            .map(|root| expand_tilde(root))
+            .collect()
+    }
+
+    /// The configuration as TOML, including every default.
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+}
+
+pub fn default_config_path() -> Option<PathBuf> {
+    if let Ok(xdg) = std::env::var("XDG_CONFIG_HOME")
+        && !xdg.is_empty()
+    {
+        return Some(
+            PathBuf::from(xdg)
+                .join("git-branch-assistant")
+                .join("config.toml"),
+        );
+    }
+    let home = std::env::var("HOME").ok()?;
+    Some(
+        PathBuf::from(home)
+            .join(".config")
+            .join("git-branch-assistant")
+            .join("config.toml"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_config_uses_defaults() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let config = Config::load_from(&temp.path().join("config.toml"))?;
+        assert!(!config.fetch);
+        Ok(())
+    }
+
+    #[test]
+    fn config_reads_fetch() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let path = temp.path().join("config.toml");
+        fs::write(&path, "fetch = true\n")?;
+        let config = Config::load_from(&path)?;
+        assert!(config.fetch);
+        Ok(())
+    }
+
+    #[test]
+    fn config_reads_every_section() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let path = temp.path().join("config.toml");
+        fs::write(
+            &path,
+            r#"
+workspace_roots = ["/src"]
+remote = "fork"
+
+[repos]
+skip_dirty_repos = true
+sort = "behind"
+group_by = "repo"
+
+[discovery]
+max_depth = 3
+ignore = ["archive"]
+
+[cache]
+ttl_secs = 60
+
+[tools]
+log = "tig --all"
+
+[default_actions]
+UpstreamGone = "Nothing"
+
+[[actions]]
+label = "Run tests"
+statuses = ["LocalAhead", "Diverged"]
+command = "cd {worktree} && cargo test"
+"#,
+        )?;
+        let config = Config::load_from(&path)?;
+        assert_eq!(config.workspace_roots(), vec![PathBuf::from("/src")]);
+        assert_eq!(config.remote, "fork");
+        assert!(config.repos.skip_dirty_repos);
+        assert!(!config.repos.dry);
+        assert_eq!(config.repos.sort, SortKey::Behind);
+        assert_eq!(config.repos.group_by, GroupBy::Repo);
+        assert_eq!(config.discovery.max_depth, 3);
+        assert!(config.discovery.follow_symlinks);
+        assert_eq!(config.discovery.ignore, vec!["archive"]);
+        assert_eq!(config.cache.ttl_secs, 60);
+        assert_eq!(config.tools.log, "tig --all");
+        assert_eq!(config.tools.gh, "gh");
+        assert_eq!(
+            config.default_actions.get(&BranchStatus::UpstreamGone),
+            Some(&BranchAction::Nothing)
+        );
+        assert_eq!(config.actions[0].label, "Run tests");
+        assert_eq!(
+            config.actions[0].statuses,
+            vec![BranchStatus::LocalAhead, BranchStatus::Diverged]
+        );
+        assert!(!config.actions[0].handled);
+        Ok(())
+    }
+
+    #[test]
+    fn config_rejects_unknown_keys() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let path = temp.path().join("config.toml");
+        fs::write(&path, "[repos]\nskip_dirty = true\n")?;
+        assert!(Config::load_from(&path).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn default_config_round_trips_through_toml() -> Result<()> {
+        let shown = Config::default().to_toml()?;
+        let parsed: Config = toml::from_str(&shown)?;
+        assert_eq!(parsed.remote, "origin");
+        assert_eq!(parsed.cache.ttl_secs, 3600);
+        Ok(())
+    }
+}
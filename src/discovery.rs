@@ -0,0 +1,313 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::config::DiscoveryConfig;
+use crate::fs_utils::is_globally_ignored;
+use crate::manifest::{MANIFEST_FILE, Manifest};
+use crate::protection::glob_match;
+
+/// A file in a workspace root listing paths to leave out of the scan, one glob per line.
+pub const IGNORE_FILE: &str = ".branch-assistant-ignore";
+
+/// Something found below a workspace root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discovered {
+    /// A repository root, or a directory at the depth limit, which is treated as one.
+    Directory(PathBuf),
+    /// A file where a repository was expected.
+    NotDirectory(PathBuf),
+}
+
+impl Discovered {
+    pub fn path(&self) -> &Path {
+        match self {
+            Discovered::Directory(path) | Discovered::NotDirectory(path) => path,
+        }
+    }
+}
+
+/// Walks workspace roots looking for repositories. Directories that aren't repositories
+/// are descended into until `max_depth`; repositories are never descended into.
+///
+/// Ignore patterns are matched against the path relative to the root. A pattern
+/// without a `/` matches an entry of that name at any depth.
+#[derive(Debug, Clone)]
+pub struct Discovery {
+    max_depth: usize,
+    follow_symlinks: bool,
+    ignore: Vec<String>,
+}
+
+impl Discovery {
+    pub fn from_config(config: &DiscoveryConfig) -> Self {
+        Self {
+            max_depth: config.max_depth.max(1),
+            follow_symlinks: config.follow_symlinks,
+            ignore: config.ignore.clone(),
+        }
+    }
+
+    /// The repositories in `roots`: those listed in a root's manifest if it has one,
+    /// otherwise everything found below it. Each repository is only included once,
+    /// even when reached through several paths.
+    pub fn discover(&self, roots: &[PathBuf]) -> Result<Vec<Discovered>> {
+        let mut walk = self.walk();
+        for root in roots {
+            match Manifest::find(root)? {
+                Some(manifest) => walk.add_manifest(root, &manifest),
+                None => walk.scan(root)?,
+            }
+        }
+        Ok(walk.found)
+    }
+
+    /// Everything found below `roots`, ignoring any manifest.
+    pub fn scan(&self, roots: &[PathBuf]) -> Result<Vec<Discovered>> {
+        let mut walk = self.walk();
+        for root in roots {
+            walk.scan(root)?;
+        }
+        Ok(walk.found)
+    }
+
+    fn walk(&self) -> Walk<'_> {
+        Walk {
+            discovery: self,
+            ignore: Vec::new(),
+            seen: HashSet::new(),
+            found: Vec::new(),
+        }
+    }
+}
+
+struct Walk<'a> {
+    discovery: &'a Discovery,
+    /// Patterns for the root being walked.
+    ignore: Vec<String>,
+    /// Canonical paths of the directories found or descended into.
+    seen: HashSet<PathBuf>,
+    found: Vec<Discovered>,
+}
+
+impl Walk<'_> {
+    fn scan(&mut self, root: &Path) -> Result<()> {
+        self.ignore = self.discovery.ignore.clone();
+        self.ignore
+            .extend(read_ignore_file(&root.join(IGNORE_FILE))?);
+        self.visit(root, Path::new(""), 1)
+    }
+
+    fn add_manifest(&mut self, root: &Path, manifest: &Manifest) {
+        for repo in &manifest.repos {
+            let path = root.join(&repo.path);
+            match path.canonicalize() {
+                Ok(canonical) if path.is_dir() => {
+                    if self.seen.insert(canonical) {
+                        self.found.push(Discovered::Directory(path));
+                    }
+                }
+                _ => eprintln!(
+                    "Not cloned yet: {} (run `git-branch-assistant sync`)",
+                    path.display()
+                ),
+            }
+        }
+    }
+
+    fn visit(&mut self, dir: &Path, relative: &Path, depth: usize) -> Result<()> {
+        let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+            .with_context(|| format!("failed to read {}", dir.display()))?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<std::io::Result<_>>()?;
+        entries.sort();
+
+        for path in entries {
+            let Some(name) = path.file_name().map(PathBuf::from) else {
+                continue;
+            };
+            let relative = relative.join(&name);
+            if is_globally_ignored(&path)
+                || name == Path::new(IGNORE_FILE)
+                || name == Path::new(MANIFEST_FILE)
+                || name == Path::new(".git")
+            {
+                continue;
+            }
+            if self.is_ignored(&relative) {
+                continue;
+            }
+            let is_symlink = fs::symlink_metadata(&path)
+                .map(|metadata| metadata.file_type().is_symlink())
+                .unwrap_or(false);
+            if is_symlink && !self.discovery.follow_symlinks {
+                continue;
+            }
+            if !path.is_dir() {
+                self.found.push(Discovered::NotDirectory(path));
+                continue;
+            }
+            let Ok(canonical) = path.canonicalize() else {
+                continue;
+            };
+            if !self.seen.insert(canonical) {
+                continue;
+            }
+            if is_repository(&path) || depth >= self.discovery.max_depth {
+                self.found.push(Discovered::Directory(path));
+            } else {
+                self.visit(&path, &relative, depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn is_ignored(&self, relative: &Path) -> bool {
+        let relative = relative.to_string_lossy();
+        let name = relative.rsplit('/').next().unwrap_or(&relative);
+        self.ignore.iter().any(|pattern| {
+            let pattern = pattern.trim_end_matches('/');
+            if pattern.contains('/') {
+                glob_match(pattern, &relative)
+            } else {
+                glob_match(pattern, name)
+            }
+        })
+    }
+}
+
+fn is_repository(dir: &Path) -> bool {
+    dir.join(".git").exists()
+}
+
+/// Patterns from an ignore file, skipping blank lines and `#` comments. A missing
+/// file has no patterns.
+fn read_ignore_file(path: &Path) -> Result<Vec<String>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("failed to read {}", path.display()));
+        }
+    };
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn discovery(max_depth: usize, follow_symlinks: bool) -> Discovery {
+        Discovery::from_config(&DiscoveryConfig {
+            max_depth,
+            follow_symlinks,
+            ignore: Vec::new(),
+        })
+    }
+
+    fn repo(path: &Path) {
+        fs::create_dir_all(path.join(".git")).unwrap();
+    }
+
+    fn directories(found: &[Discovered], root: &Path) -> Vec<String> {
+        found
+            .iter()
+            .filter_map(|found| match found {
+                Discovered::Directory(path) => Some(
+                    path.strip_prefix(root)
+                        .unwrap()
+                        .to_string_lossy()
+                        .into_owned(),
+                ),
+                Discovered::NotDirectory(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn walks_to_depth_and_stops_at_repositories() -> Result<()> {
+        let temp = tempdir()?;
+        let root = temp.path();
+        repo(&root.join("top"));
+        repo(&root.join("top/nested"));
+        repo(&root.join("org/a"));
+        repo(&root.join("org/deeper/b"));
+        fs::write(root.join("notes.txt"), "")?;
+
+        let found = discovery(2, true).discover(&[root.to_path_buf()])?;
+
+        assert_eq!(
+            directories(&found, root),
+            vec!["org/a", "org/deeper", "top"]
+        );
+        assert!(found.contains(&Discovered::NotDirectory(root.join("notes.txt"))));
+
+        let found = discovery(1, true).discover(&[root.to_path_buf()])?;
+        assert_eq!(directories(&found, root), vec!["org", "top"]);
+        Ok(())
+    }
+
+    #[test]
+    fn honours_ignore_file() -> Result<()> {
+        let temp = tempdir()?;
+        let root = temp.path();
+        repo(&root.join("org/a"));
+        repo(&root.join("org/archive-1"));
+        repo(&root.join("vendor/c"));
+        fs::write(
+            root.join(IGNORE_FILE),
+            "# old stuff\norg/archive-*\nvendor\n",
+        )?;
+
+        let found = discovery(3, true).discover(&[root.to_path_buf()])?;
+
+        assert_eq!(directories(&found, root), vec!["org/a"]);
+        Ok(())
+    }
+
+    #[test]
+    fn manifest_replaces_scanning() -> Result<()> {
+        let temp = tempdir()?;
+        let root = temp.path();
+        repo(&root.join("org/a"));
+        repo(&root.join("stray"));
+        fs::write(
+            root.join(MANIFEST_FILE),
+            "[[repo]]\npath = \"org/a\"\nurl = \"u\"\n\n[[repo]]\npath = \"missing\"\nurl = \"u\"\n",
+        )?;
+
+        let found = discovery(3, true).discover(&[root.to_path_buf()])?;
+        assert_eq!(directories(&found, root), vec!["org/a"]);
+
+        let found = discovery(3, true).scan(&[root.to_path_buf()])?;
+        assert_eq!(directories(&found, root), vec!["org/a", "stray"]);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_or_skips_symlinks_and_deduplicates() -> Result<()> {
+        let temp = tempdir()?;
+        let root = temp.path();
+        repo(&root.join("a"));
+        std::os::unix::fs::symlink(root.join("a"), root.join("link-to-a"))?;
+        let elsewhere = tempdir()?;
+        repo(&elsewhere.path().join("b"));
+        std::os::unix::fs::symlink(elsewhere.path().join("b"), root.join("link-to-b"))?;
+
+        let found = discovery(1, true).discover(&[root.to_path_buf(), root.to_path_buf()])?;
+        assert_eq!(directories(&found, root), vec!["a", "link-to-b"]);
+
+        let found = discovery(1, false).discover(&[root.to_path_buf()])?;
+        assert_eq!(directories(&found, root), vec!["a"]);
+        Ok(())
+    }
+}
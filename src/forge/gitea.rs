@@ -0,0 +1,155 @@
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+
+use super::{
+    Forge, ForgeKind, NewPullRequest, PULL_REQUEST_LIMIT, PullRequest, PullRequestState,
+    PullRequests, head_label, latest_by_head, remote_owner,
+};
+use crate::git::GitRepo;
+
+/// Gitea and Forgejo, through `tea`.
+pub struct Gitea {
+    command: String,
+}
+
+impl Gitea {
+    pub fn new(command: String) -> Self {
+        Self { command }
+    }
+
+    fn repository(&self, repo: &GitRepo) -> Result<TeaRepo> {
+        let output = repo.read_tool(&self.command, &["repo", "--output", "json"])?;
+        serde_json::from_str(&output).context("failed to parse tea repo output")
+    }
+}
+
+impl Forge for Gitea {
+    fn kind(&self) -> ForgeKind {
+        ForgeKind::Gitea
+    }
+
+    /// tea has no draft flag, so drafts get Gitea's `WIP:` title prefix instead, and
+    /// it can't request reviewers.
+    fn create_pull_request(&self, repo: &GitRepo, pull: &NewPullRequest) -> Result<()> {
+        let title = if pull.draft {
+            format!("WIP: {}", pull.title)
+        } else {
+            pull.title.clone()
+        };
+        let labels = pull.labels.join(",");
+        let mut args = vec![
+            "pulls",
+            "create",
+            "--head",
+            &pull.branch,
+            "--base",
+            &pull.base,
+            "--title",
+            &title,
+            "--description",
+            &pull.body,
+        ];
+        if !pull.labels.is_empty() {
+            args.extend(["--labels", &labels]);
+        }
+        if !pull.reviewers.is_empty() {
+            eprintln!(
+                "Request reviews from {} on Gitea; tea can't.",
+                pull.reviewers.join(", ")
+            );
+        }
+        repo.run_tool(&self.command, &args)
+    }
+
+    fn open_pull_request(&self, repo: &GitRepo, number: u64) -> Result<()> {
+        let number = number.to_string();
+        repo.run_tool(&self.command, &["open", &number])
+    }
+
+    fn pull_requests(&self, repo: &GitRepo) -> Result<PullRequests> {
+        let output = repo.read_tool(
+            &self.command,
+            &[
+                "pulls",
+                "list",
+                "--state",
+                "all",
+                "--limit",
+                PULL_REQUEST_LIMIT,
+                "--output",
+                "json",
+                "--fields",
+                "index,state,head,url",
+            ],
+        )?;
+        let pulls: Vec<TeaPullRequest> =
+            serde_json::from_str(&output).context("failed to parse tea pulls list output")?;
+        // tea doesn't tell which repository a pull request is from, so its branch is
+        // taken to be in the repository the pull request is made to.
+        let pulls = pulls
+            .into_iter()
+            .filter_map(|pull| {
+                let owner = remote_owner(pull.url.rsplit_once("/pulls/")?.0)?;
+                let head = head_label(owner, &pull.head);
+                Some(pull.into_pull_request().map(|pull| (head, pull)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(latest_by_head(pulls))
+    }
+
+    /// tea can't filter by branch, so this lists them all and picks ours.
+    fn pull_request_for_branch(
+        &self,
+        repo: &GitRepo,
+        owner: &str,
+        branch: &str,
+    ) -> Result<Option<PullRequest>> {
+        Ok(self.pull_requests(repo)?.remove(&head_label(owner, branch)))
+    }
+
+    fn default_branch(&self, repo: &GitRepo) -> Result<String> {
+        self.repository(repo)?
+            .default_branch
+            .filter(|branch| !branch.is_empty())
+            .ok_or_else(|| anyhow!("tea did not report a default branch"))
+    }
+
+    fn web_url(&self, repo: &GitRepo) -> Result<String> {
+        Ok(self.repository(repo)?.html_url)
+    }
+}
+
+/// tea prints every field as a string.
+#[derive(Deserialize)]
+struct TeaPullRequest {
+    index: String,
+    state: String,
+    head: String,
+    url: String,
+}
+
+impl TeaPullRequest {
+    fn into_pull_request(self) -> Result<PullRequest> {
+        let number = self
+            .index
+            .parse()
+            .with_context(|| format!("unexpected pull request index {}", self.index))?;
+        Ok(PullRequest {
+            number,
+            state: match self.state.as_str() {
+                "merged" => PullRequestState::Merged,
+                "closed" => PullRequestState::Closed,
+                _ => PullRequestState::Open,
+            },
+            url: self.url,
+            review: None,
+            head_sha: None,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct TeaRepo {
+    default_branch: Option<String>,
+    html_url: String,
+}
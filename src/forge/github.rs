@@ -0,0 +1,182 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use super::{
+    Forge, ForgeKind, NewPullRequest, PULL_REQUEST_LIMIT, PullRequest, PullRequestState,
+    PullRequests, ReviewStatus, head_label, latest_by_head,
+};
+use crate::git::GitRepo;
+
+/// GitHub, through `gh`.
+pub struct GitHub {
+    command: String,
+}
+
+impl GitHub {
+    pub fn new(command: String) -> Self {
+        Self { command }
+    }
+}
+
+impl Forge for GitHub {
+    fn kind(&self) -> ForgeKind {
+        ForgeKind::GitHub
+    }
+
+    fn create_pull_request(&self, repo: &GitRepo, pull: &NewPullRequest) -> Result<()> {
+        let mut args = vec![
+            "pr",
+            "create",
+            "--head",
+            &pull.branch,
+            "--base",
+            &pull.base,
+            "--title",
+            &pull.title,
+            "--body",
+            &pull.body,
+        ];
+        if pull.draft {
+            args.push("--draft");
+        }
+        for reviewer in &pull.reviewers {
+            args.extend(["--reviewer", reviewer]);
+        }
+        for label in &pull.labels {
+            args.extend(["--label", label]);
+        }
+        repo.run_tool(&self.command, &args)
+    }
+
+    fn open_pull_request(&self, repo: &GitRepo, number: u64) -> Result<()> {
+        let number = number.to_string();
+        repo.run_tool(&self.command, &["pr", "view", &number, "--web"])
+    }
+
+    fn pull_requests(&self, repo: &GitRepo) -> Result<PullRequests> {
+        let output = repo.read_tool(
+            &self.command,
+            &[
+                "pr",
+                "list",
+                "--state",
+                "all",
+                "--limit",
+                PULL_REQUEST_LIMIT,
+                "--json",
+                "number,state,isDraft,url,headRefName,headRefOid,headRepositoryOwner,reviewDecision",
+            ],
+        )?;
+        by_head(&output)
+    }
+
+    /// `--head` only takes a branch name, so pull requests from forks that use the
+    /// same name are told apart by their owner here.
+    fn pull_request_for_branch(
+        &self,
+        repo: &GitRepo,
+        owner: &str,
+        branch: &str,
+    ) -> Result<Option<PullRequest>> {
+        let output = repo.read_tool(
+            &self.command,
+            &[
+                "pr",
+                "list",
+                "--head",
+                branch,
+                "--state",
+                "all",
+                "--json",
+                "number,state,isDraft,url,headRefName,headRefOid,headRepositoryOwner,reviewDecision",
+            ],
+        )?;
+        Ok(by_head(&output)?.remove(&head_label(owner, branch)))
+    }
+
+    fn default_branch(&self, repo: &GitRepo) -> Result<String> {
+        let output = repo.read_tool(
+            &self.command,
+            &["repo", "view", "--json", "defaultBranchRef"],
+        )?;
+        let response: GhRepo =
+            serde_json::from_str(&output).context("failed to parse gh repo view output")?;
+        Ok(response.default_branch_ref.name.trim().to_string())
+    }
+
+    fn web_url(&self, repo: &GitRepo) -> Result<String> {
+        let output = repo.read_tool(&self.command, &["repo", "view", "--json", "url"])?;
+        let response: GhRepo =
+            serde_json::from_str(&output).context("failed to parse gh repo view output")?;
+        Ok(response.url)
+    }
+}
+
+/// The latest of the pull requests `gh pr list` printed for each head.
+fn by_head(output: &str) -> Result<PullRequests> {
+    let pulls: Vec<GhPullRequest> =
+        serde_json::from_str(output).context("failed to parse gh pr list output")?;
+    // A pull request whose fork was deleted has no owner, and no branch to match.
+    Ok(latest_by_head(pulls.into_iter().filter_map(|pull| {
+        let owner = pull.head_repository_owner.as_ref()?;
+        let head = head_label(&owner.login, &pull.head_ref_name);
+        Some((head, pull.into_pull_request()))
+    })))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GhPullRequest {
+    number: u64,
+    state: String,
+    #[serde(default)]
+    is_draft: bool,
+    url: String,
+    #[serde(default)]
+    head_ref_name: String,
+    #[serde(default)]
+    head_ref_oid: Option<String>,
+    #[serde(default)]
+    head_repository_owner: Option<GhOwner>,
+    #[serde(default)]
+    review_decision: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GhOwner {
+    login: String,
+}
+
+impl GhPullRequest {
+    fn into_pull_request(self) -> PullRequest {
+        PullRequest {
+            number: self.number,
+            state: match self.state.as_str() {
+                "MERGED" => PullRequestState::Merged,
+                "CLOSED" => PullRequestState::Closed,
+                _ if self.is_draft => PullRequestState::Draft,
+                _ => PullRequestState::Open,
+            },
+            url: self.url,
+            review: match self.review_decision.as_deref() {
+                Some("APPROVED") => Some(ReviewStatus::Approved),
+                Some("CHANGES_REQUESTED") => Some(ReviewStatus::ChangesRequested),
+                Some("REVIEW_REQUIRED") => Some(ReviewStatus::ReviewRequired),
+                _ => None,
+            },
+            head_sha: self.head_ref_oid.filter(|sha| !sha.is_empty()),
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct GhRepo {
+    default_branch_ref: GhBranchRef,
+    url: String,
+}
+
+#[derive(Deserialize, Default)]
+struct GhBranchRef {
+    name: String,
+}
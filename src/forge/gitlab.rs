@@ -0,0 +1,165 @@
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+
+use super::{
+    Forge, ForgeKind, NewPullRequest, PULL_REQUEST_LIMIT, PullRequest, PullRequestState,
+    PullRequests, head_label, latest_by_head, remote_owner,
+};
+use crate::git::GitRepo;
+
+/// GitLab, through `glab`. Pull requests are merge requests there.
+pub struct GitLab {
+    command: String,
+}
+
+impl GitLab {
+    pub fn new(command: String) -> Self {
+        Self { command }
+    }
+
+    fn project(&self, repo: &GitRepo) -> Result<GlabProject> {
+        let output = repo.read_tool(&self.command, &["repo", "view", "--output", "json"])?;
+        serde_json::from_str(&output).context("failed to parse glab repo view output")
+    }
+}
+
+impl Forge for GitLab {
+    fn kind(&self) -> ForgeKind {
+        ForgeKind::GitLab
+    }
+
+    fn create_pull_request(&self, repo: &GitRepo, pull: &NewPullRequest) -> Result<()> {
+        let mut args = vec![
+            "mr",
+            "create",
+            "--source-branch",
+            &pull.branch,
+            "--target-branch",
+            &pull.base,
+            "--title",
+            &pull.title,
+            "--description",
+            &pull.body,
+            "--yes",
+        ];
+        if pull.draft {
+            args.push("--draft");
+        }
+        for reviewer in &pull.reviewers {
+            args.extend(["--reviewer", reviewer]);
+        }
+        for label in &pull.labels {
+            args.extend(["--label", label]);
+        }
+        repo.run_tool(&self.command, &args)
+    }
+
+    fn open_pull_request(&self, repo: &GitRepo, number: u64) -> Result<()> {
+        let number = number.to_string();
+        repo.run_tool(&self.command, &["mr", "view", &number, "--web"])
+    }
+
+    fn pull_requests(&self, repo: &GitRepo) -> Result<PullRequests> {
+        let output = repo.read_tool(
+            &self.command,
+            &[
+                "mr",
+                "list",
+                "--all",
+                "--per-page",
+                PULL_REQUEST_LIMIT,
+                "--output",
+                "json",
+            ],
+        )?;
+        by_head(&output)
+    }
+
+    fn pull_request_for_branch(
+        &self,
+        repo: &GitRepo,
+        owner: &str,
+        branch: &str,
+    ) -> Result<Option<PullRequest>> {
+        let output = repo.read_tool(
+            &self.command,
+            &[
+                "mr",
+                "list",
+                "--source-branch",
+                branch,
+                "--all",
+                "--output",
+                "json",
+            ],
+        )?;
+        Ok(by_head(&output)?.remove(&head_label(owner, branch)))
+    }
+
+    fn default_branch(&self, repo: &GitRepo) -> Result<String> {
+        self.project(repo)?
+            .default_branch
+            .filter(|branch| !branch.is_empty())
+            .ok_or_else(|| anyhow!("glab did not report a default branch"))
+    }
+
+    fn web_url(&self, repo: &GitRepo) -> Result<String> {
+        Ok(self.project(repo)?.web_url)
+    }
+}
+
+/// The latest of the merge requests `glab mr list` printed for each head.
+fn by_head(output: &str) -> Result<PullRequests> {
+    let requests: Vec<GlabMergeRequest> =
+        serde_json::from_str(output).context("failed to parse glab mr list output")?;
+    // glab only tells which project a merge request is from by its id, so those
+    // from forks, whose owner it doesn't name, are left out.
+    Ok(latest_by_head(requests.into_iter().filter_map(|request| {
+        if request.source_project_id != request.target_project_id {
+            return None;
+        }
+        let project_url = request.web_url.split_once("/-/")?.0;
+        let head = head_label(remote_owner(project_url)?, &request.source_branch);
+        Some((head, request.into_pull_request()))
+    })))
+}
+
+#[derive(Deserialize)]
+struct GlabMergeRequest {
+    iid: u64,
+    state: String,
+    #[serde(default)]
+    draft: bool,
+    web_url: String,
+    #[serde(default)]
+    source_branch: String,
+    #[serde(default)]
+    source_project_id: Option<u64>,
+    #[serde(default)]
+    target_project_id: Option<u64>,
+    #[serde(default)]
+    sha: Option<String>,
+}
+
+impl GlabMergeRequest {
+    fn into_pull_request(self) -> PullRequest {
+        PullRequest {
+            number: self.iid,
+            state: match self.state.as_str() {
+                "merged" => PullRequestState::Merged,
+                "closed" | "locked" => PullRequestState::Closed,
+                _ if self.draft => PullRequestState::Draft,
+                _ => PullRequestState::Open,
+            },
+            url: self.web_url,
+            review: None,
+            head_sha: self.sha.filter(|sha| !sha.is_empty()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GlabProject {
+    default_branch: Option<String>,
+    web_url: String,
+}
@@ -0,0 +1,534 @@
+//! Pull requests and repository metadata from the forge a repository is hosted on,
+//! through the forge's command line tool.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::config::ToolsConfig;
+use crate::git::{CommitMessage, GitRepo};
+
+mod gitea;
+mod github;
+mod gitlab;
+
+pub use gitea::Gitea;
+pub use github::GitHub;
+pub use gitlab::GitLab;
+
+/// A forge's operations, each run through `repo` so that dry runs and the journal see
+/// the commands.
+pub trait Forge: Send + Sync {
+    fn kind(&self) -> ForgeKind;
+
+    /// Opens a pull request (a merge request, on GitLab).
+    fn create_pull_request(&self, repo: &GitRepo, pull: &NewPullRequest) -> Result<()>;
+
+    /// Shows pull request `number` in the web browser.
+    fn open_pull_request(&self, repo: &GitRepo, number: u64) -> Result<()>;
+
+    /// The most recent pull request from each branch that has one, in any state,
+    /// looked up in one go. Pull requests from forks are told apart by the fork's owner.
+    fn pull_requests(&self, repo: &GitRepo) -> Result<PullRequests>;
+
+    /// The most recent pull request from `branch` in `owner`'s repository or fork, in
+    /// any state.
+    fn pull_request_for_branch(
+        &self,
+        repo: &GitRepo,
+        owner: &str,
+        branch: &str,
+    ) -> Result<Option<PullRequest>>;
+
+    fn default_branch(&self, repo: &GitRepo) -> Result<String>;
+
+    /// The repository's page on the forge.
+    fn web_url(&self, repo: &GitRepo) -> Result<String>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForgeKind {
+    GitHub,
+    GitLab,
+    Gitea,
+}
+
+impl ForgeKind {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "github" => Some(ForgeKind::GitHub),
+            "gitlab" => Some(ForgeKind::GitLab),
+            "gitea" | "forgejo" => Some(ForgeKind::Gitea),
+            _ => None,
+        }
+    }
+
+    /// Guesses the forge from the host in a remote URL, defaulting to GitHub.
+    pub fn from_remote_url(url: &str) -> Self {
+        let host = remote_host(url).to_ascii_lowercase();
+        if host.contains("gitlab") {
+            ForgeKind::GitLab
+        } else if host.contains("gitea") || host.contains("forgejo") || host == "codeberg.org" {
+            ForgeKind::Gitea
+        } else {
+            ForgeKind::GitHub
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ForgeKind::GitHub => "GitHub",
+            ForgeKind::GitLab => "GitLab",
+            ForgeKind::Gitea => "Gitea",
+        }
+    }
+
+    /// The forge implementation, driving the tool configured for it.
+    pub fn forge(&self, tools: &ToolsConfig) -> Box<dyn Forge> {
+        match self {
+            ForgeKind::GitHub => Box::new(GitHub::new(tools.gh.clone())),
+            ForgeKind::GitLab => Box::new(GitLab::new(tools.glab.clone())),
+            ForgeKind::Gitea => Box::new(Gitea::new(tools.tea.clone())),
+        }
+    }
+}
+
+/// A pull request to open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewPullRequest {
+    pub branch: String,
+    pub base: String,
+    pub title: String,
+    pub body: String,
+    pub draft: bool,
+    pub reviewers: Vec<String>,
+    pub labels: Vec<String>,
+}
+
+impl NewPullRequest {
+    /// A pull request from `branch` into `base`, titled and described after the
+    /// branch's commits: a single commit's message as it is, or else the branch name
+    /// and a list of the commits' subjects.
+    pub fn new(branch: &str, base: &str, commits: &[CommitMessage]) -> Self {
+        let (title, body) = match commits {
+            [commit] => (commit.subject.clone(), commit.body.clone()),
+            _ => (
+                branch.to_string(),
+                commits
+                    .iter()
+                    .map(|commit| format!("- {}\n", commit.subject))
+                    .collect(),
+            ),
+        };
+        Self {
+            branch: branch.to_string(),
+            base: base.to_string(),
+            title,
+            body,
+            draft: false,
+            reviewers: Vec::new(),
+            labels: Vec::new(),
+        }
+    }
+}
+
+/// How many of the most recent pull requests are looked at when looking them up for
+/// every branch at once.
+const PULL_REQUEST_LIMIT: &str = "200";
+
+/// Pull requests by the branch they are from, labelled with [`head_label`].
+pub type PullRequests = HashMap<String, PullRequest>;
+
+/// Names a branch in the repository or fork owned by `owner`, as GitHub does with
+/// `owner:branch`. Owners are compared ignoring case.
+pub fn head_label(owner: &str, branch: &str) -> String {
+    format!("{}:{branch}", owner.to_lowercase())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PullRequest {
+    pub number: u64,
+    pub state: PullRequestState,
+    pub url: String,
+    /// Where review stands, for forges that tell.
+    #[serde(default)]
+    pub review: Option<ReviewStatus>,
+    /// The commit the pull request's branch pointed to last, for forges that tell.
+    #[serde(default)]
+    pub head_sha: Option<String>,
+}
+
+impl PullRequest {
+    /// Such as `#12 open, approved`.
+    pub fn summary(&self) -> String {
+        match self.review {
+            Some(review) if self.state == PullRequestState::Open => {
+                format!(
+                    "#{} {}, {}",
+                    self.number,
+                    self.state.label(),
+                    review.label()
+                )
+            }
+            _ => format!("#{} {}", self.number, self.state.label()),
+        }
+    }
+}
+
+/// Keeps the most recent pull request for each head label.
+fn latest_by_head(pulls: impl IntoIterator<Item = (String, PullRequest)>) -> PullRequests {
+    let mut latest = PullRequests::new();
+    for (head, pull) in pulls {
+        match latest.get(&head) {
+            Some(existing) if existing.number > pull.number => {}
+            _ => {
+                latest.insert(head, pull);
+            }
+        }
+    }
+    latest
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PullRequestState {
+    Open,
+    Draft,
+    Merged,
+    Closed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReviewStatus {
+    ReviewRequired,
+    ChangesRequested,
+    Approved,
+}
+
+impl ReviewStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ReviewStatus::ReviewRequired => "review required",
+            ReviewStatus::ChangesRequested => "changes requested",
+            ReviewStatus::Approved => "approved",
+        }
+    }
+}
+
+impl PullRequestState {
+    /// Whether the pull request is still open, as a draft or not.
+    pub fn is_active(&self) -> bool {
+        matches!(self, PullRequestState::Open | PullRequestState::Draft)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PullRequestState::Open => "open",
+            PullRequestState::Draft => "draft",
+            PullRequestState::Merged => "merged",
+            PullRequestState::Closed => "closed",
+        }
+    }
+}
+
+/// The host part of `https://host/path`, `ssh://user@host:port/path` or
+/// `user@host:path`.
+fn remote_host(url: &str) -> &str {
+    let rest = match url.split_once("://") {
+        Some((_, rest)) => rest,
+        None => url,
+    };
+    let rest = rest.rsplit_once('@').map_or(rest, |(_, host)| host);
+    rest.split(['/', ':']).next().unwrap_or(rest)
+}
+
+/// Who owns the repository at a remote or web URL: everything in its path but the
+/// repository's own name, such as `o` for `git@github.com:o/r.git` or `group/sub` for
+/// `https://gitlab.com/group/sub/r`.
+pub fn remote_owner(url: &str) -> Option<&str> {
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest.split_once('/')?.1,
+        None => url.split_once(':')?.1,
+    };
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    path.rsplit_once('/')
+        .map(|(owner, _)| owner)
+        .filter(|owner| !owner.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PullRequestsConfig;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+    use tempfile::tempdir;
+
+    /// An executable that records its arguments in `<name>.log` and prints `output`.
+    #[cfg(unix)]
+    fn stub(dir: &Path, name: &str, output: &str) -> String {
+        use std::os::unix::fs::PermissionsExt;
+        let path = dir.join(name);
+        let log = dir.join(format!("{name}.log"));
+        fs::write(
+            &path,
+            format!(
+                "#!/bin/sh\necho \"$@\" >> '{}'\ncat <<'EOF'\n{output}\nEOF\n",
+                log.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn stub_log(dir: &Path, name: &str) -> String {
+        fs::read_to_string(dir.join(format!("{name}.log"))).unwrap_or_default()
+    }
+
+    fn repo_with_remote(dir: &Path, url: &str, tools: ToolsConfig) -> GitRepo {
+        for args in [
+            vec!["init", "-q", "-b", "main"],
+            vec!["remote", "add", "origin", url],
+        ] {
+            let status = Command::new("git")
+                .args(&args)
+                .current_dir(dir)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+        GitRepo::new(PathBuf::from(dir)).with_tools(tools)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn gitlab_merge_requests_are_looked_up_with_glab() -> Result<()> {
+        let temp = tempdir()?;
+        let bin = tempdir()?;
+        let tools = ToolsConfig {
+            glab: stub(
+                bin.path(),
+                "glab",
+                r#"[{"iid": 3, "state": "merged", "draft": false, "web_url": "https://gitlab.com/o/r/-/merge_requests/3", "source_branch": "feature"},
+                    {"iid": 9, "state": "opened", "draft": false, "web_url": "https://gitlab.com/o/r/-/merge_requests/9", "source_branch": "feature", "source_project_id": 2, "target_project_id": 1},
+                    {"iid": 7, "state": "opened", "draft": true, "web_url": "https://gitlab.com/o/r/-/merge_requests/7", "source_branch": "feature"}]"#,
+            ),
+            ..ToolsConfig::default()
+        };
+        let repo = repo_with_remote(temp.path(), "git@gitlab.com:o/r.git", tools);
+
+        let forge = repo.forge();
+        assert_eq!(forge.kind(), ForgeKind::GitLab);
+        assert_eq!(
+            forge.pull_request_for_branch(&repo, "o", "feature")?,
+            Some(PullRequest {
+                number: 7,
+                state: PullRequestState::Draft,
+                url: "https://gitlab.com/o/r/-/merge_requests/7".into(),
+                review: None,
+                head_sha: None,
+            })
+        );
+        assert_eq!(
+            stub_log(bin.path(), "glab").trim(),
+            "mr list --source-branch feature --all --output json"
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn github_pull_requests_are_looked_up_for_all_branches_at_once() -> Result<()> {
+        let temp = tempdir()?;
+        let bin = tempdir()?;
+        let tools = ToolsConfig {
+            gh: stub(
+                bin.path(),
+                "gh",
+                r#"[{"number": 15, "state": "MERGED", "isDraft": false, "url": "u15", "headRefName": "feature", "headRepositoryOwner": {"login": "stranger"}},
+                    {"number": 12, "state": "OPEN", "isDraft": false, "url": "u12", "headRefName": "feature", "headRepositoryOwner": {"login": "o"}, "reviewDecision": "CHANGES_REQUESTED"},
+                    {"number": 10, "state": "MERGED", "isDraft": false, "url": "u10", "headRefName": "old", "headRepositoryOwner": {"login": "O"}, "reviewDecision": ""},
+                    {"number": 4, "state": "CLOSED", "isDraft": false, "url": "u4", "headRefName": "feature", "headRepositoryOwner": {"login": "o"}, "reviewDecision": null},
+                    {"number": 2, "state": "CLOSED", "isDraft": false, "url": "u2", "headRefName": "gone", "headRepositoryOwner": null}]"#,
+            ),
+            ..ToolsConfig::default()
+        };
+        let repo = repo_with_remote(temp.path(), "git@github.com:o/r.git", tools);
+
+        let pulls = repo.pull_requests()?;
+        assert_eq!(pulls.len(), 3);
+        assert_eq!(pulls["o:feature"].summary(), "#12 open, changes requested");
+        assert_eq!(pulls["o:old"].summary(), "#10 merged");
+        assert_eq!(pulls["stranger:feature"].summary(), "#15 merged");
+        assert_eq!(stub_log(bin.path(), "gh").lines().count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn repository_without_remote_has_no_pull_requests() -> Result<()> {
+        let temp = tempdir()?;
+        Command::new("git")
+            .args(["init", "-q"])
+            .current_dir(temp.path())
+            .status()?;
+        let tools = ToolsConfig {
+            gh: "/nonexistent/gh".into(),
+            ..ToolsConfig::default()
+        };
+        let repo = GitRepo::new(temp.path().to_path_buf()).with_tools(tools);
+        assert!(repo.pull_requests()?.is_empty());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pull_request_is_only_created_when_none_is_open() -> Result<()> {
+        let temp = tempdir()?;
+        let bin = tempdir()?;
+        let tools = ToolsConfig {
+            gh: stub(bin.path(), "gh", "[]"),
+            ..ToolsConfig::default()
+        };
+        let repo = repo_with_remote(temp.path(), "https://github.com/o/r.git", tools)
+            .with_pull_request_defaults(PullRequestsConfig {
+                draft: true,
+                reviewers: vec!["alice".into()],
+                labels: vec!["backend".into()],
+                ..PullRequestsConfig::default()
+            });
+        for args in [
+            &["config", "branch-assistant.defaultBranch", "main"][..],
+            &["commit", "-q", "--allow-empty", "-m", "initial"],
+            &["checkout", "-q", "-b", "feature"],
+            &["commit", "-q", "--allow-empty", "-m", "Add login"],
+            &["commit", "-q", "--allow-empty", "-m", "Fix typo"],
+        ] {
+            let status = Command::new("git")
+                .args(args)
+                .current_dir(temp.path())
+                .env("GIT_AUTHOR_NAME", "Test")
+                .env("GIT_AUTHOR_EMAIL", "test@example.com")
+                .env("GIT_COMMITTER_NAME", "Test")
+                .env("GIT_COMMITTER_EMAIL", "test@example.com")
+                .status()?;
+            assert!(status.success());
+        }
+
+        repo.create_pull_request("feature", "origin", "main")?;
+        let log = stub_log(bin.path(), "gh");
+        assert!(
+            log.contains(
+                "pr create --head feature --base main --title feature --body - Add login\n- Fix typo\n"
+            ),
+            "{log}"
+        );
+        assert!(
+            log.contains(" --draft --reviewer alice --label backend"),
+            "{log}"
+        );
+
+        let open = r#"[{"number": 9, "state": "OPEN", "isDraft": false, "url": "https://github.com/o/r/pull/9", "headRefName": "feature", "headRepositoryOwner": {"login": "o"}}]"#;
+        let tools = ToolsConfig {
+            gh: stub(bin.path(), "gh-open", open),
+            ..ToolsConfig::default()
+        };
+        let repo = GitRepo::new(temp.path().to_path_buf()).with_tools(tools);
+        repo.create_pull_request("feature", "origin", "main")?;
+        assert!(!stub_log(bin.path(), "gh-open").contains("pr create"));
+
+        // Someone else's fork using the same branch name doesn't count.
+        let fork = open.replace(r#""login": "o""#, r#""login": "stranger""#);
+        let tools = ToolsConfig {
+            gh: stub(bin.path(), "gh-fork", &fork),
+            ..ToolsConfig::default()
+        };
+        let repo = GitRepo::new(temp.path().to_path_buf()).with_tools(tools);
+        assert_eq!(repo.pull_request_for_branch("origin", "feature")?, None);
+        repo.create_pull_request("feature", "origin", "main")?;
+        assert!(stub_log(bin.path(), "gh-fork").contains("pr create --head feature"));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn forge_can_be_set_in_git_config() -> Result<()> {
+        let temp = tempdir()?;
+        let bin = tempdir()?;
+        let tools = ToolsConfig {
+            tea: stub(
+                bin.path(),
+                "tea",
+                r#"[{"index": "4", "state": "closed", "head": "other", "url": "https://git.example.com/o/r/pulls/4"},
+                    {"index": "5", "state": "open", "head": "feature", "url": "https://git.example.com/o/r/pulls/5"}]"#,
+            ),
+            ..ToolsConfig::default()
+        };
+        let repo = repo_with_remote(temp.path(), "git@git.example.com:o/r.git", tools);
+        Command::new("git")
+            .args(["config", "branch-assistant.forge", "gitea"])
+            .current_dir(temp.path())
+            .status()?;
+
+        let pull = repo
+            .forge()
+            .pull_request_for_branch(&repo, "o", "feature")?;
+        assert_eq!(pull.map(|pull| pull.number), Some(5));
+        Ok(())
+    }
+
+    #[test]
+    fn remote_owner_is_the_path_without_the_repository() {
+        assert_eq!(remote_owner("git@github.com:o/r.git"), Some("o"));
+        assert_eq!(remote_owner("https://github.com/o/r"), Some("o"));
+        assert_eq!(
+            remote_owner("ssh://git@host:22/group/sub/r.git"),
+            Some("group/sub")
+        );
+        assert_eq!(remote_owner("/srv/git/r.git"), None);
+    }
+
+    #[test]
+    fn new_pull_request_is_described_after_its_commits() {
+        let commit = |subject: &str, body: &str| CommitMessage {
+            subject: subject.into(),
+            body: body.into(),
+        };
+        let single = NewPullRequest::new(
+            "feature",
+            "main",
+            &[commit("Add login", "Users can log in.")],
+        );
+        assert_eq!(single.title, "Add login");
+        assert_eq!(single.body, "Users can log in.");
+
+        let several = NewPullRequest::new(
+            "feature",
+            "stack-base",
+            &[commit("Add login", "Details"), commit("Fix typo", "")],
+        );
+        assert_eq!(several.title, "feature");
+        assert_eq!(several.body, "- Add login\n- Fix typo\n");
+        assert_eq!(several.base, "stack-base");
+    }
+
+    #[test]
+    fn forge_is_guessed_from_remote_url() {
+        for (url, kind) in [
+            ("git@github.com:org/repo.git", ForgeKind::GitHub),
+            ("https://gitlab.com/org/repo.git", ForgeKind::GitLab),
+            (
+                "ssh://git@gitlab.example.com:2222/org/repo",
+                ForgeKind::GitLab,
+            ),
+            ("https://codeberg.org/org/repo", ForgeKind::Gitea),
+            ("git@gitea.internal:org/repo.git", ForgeKind::Gitea),
+            ("/srv/git/repo.git", ForgeKind::GitHub),
+        ] {
+            assert_eq!(ForgeKind::from_remote_url(url), kind, "{url}");
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result, anyhow};
+
+pub type SharedBackend = Arc<dyn GitBackend>;
+
+/// How the output of a mutating command is handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    /// Captured, and only reported if the command fails.
+    Captured,
+    /// Echoed as a command line and passed through to the terminal.
+    Shown,
+}
+
+/// Executes the git (and forge) commands issued by [`super::GitRepo`]. Commands are
+/// split by intent so that a backend can let reads through while intercepting writes.
+pub trait GitBackend: Send + Sync {
+    /// Runs a command that only inspects state and returns its stdout.
+    fn read(&self, dir: &Path, program: &str, args: &[&str]) -> Result<String>;
+
+    /// Runs a command that only inspects state and reports whether it succeeded.
+    fn check(&self, dir: &Path, program: &str, args: &[&str]) -> Result<bool>;
+
+    /// Runs a command that changes the repository, a remote or a forge.
+    fn write(&self, dir: &Path, program: &str, args: &[&str], output: Output) -> Result<()>;
+
+    /// Hands the terminal over to an interactive viewer such as `tig`.
+    fn interactive(&self, dir: &Path, program: &str, args: &[&str]) -> Result<()>;
+}
+
+/// Runs every command as a child process.
+#[derive(Default)]
+pub struct ProcessBackend;
+
+impl ProcessBackend {
+    pub fn shared() -> SharedBackend {
+        Arc::new(Self)
+    }
+
+    fn command(dir: &Path, program: &str) -> Command {
+        let mut command = Command::new(program);
+        command.current_dir(dir);
+        command
+    }
+
+    fn run_inheriting(dir: &Path, program: &str, args: &[&str]) -> Result<()> {
+        let status = Self::command(dir, program)
+            .args(args)
+            .status()
+            .with_context(|| format!("failed to run {}", format_command(program, args)))?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "{} exited with status {}",
+                format_command(program, args),
+                status
+            ))
+        }
+    }
+}
+
+impl GitBackend for ProcessBackend {
+    fn read(&self, dir: &Path, program: &str, args: &[&str]) -> Result<String> {
+        let output = Self::command(dir, program)
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .with_context(|| format!("failed to run {}", format_command(program, args)))?;
+
+        if output.status.success() {
+            Ok(String::from_utf8(output.stdout).context("command output was not valid UTF-8")?)
+        } else {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            Err(anyhow!(
+                "{} failed (stdout: {}, stderr: {})",
+                format_command(program, args),
+                stdout.trim(),
+                stderr.trim()
+            ))
+        }
+    }
+
+    fn check(&self, dir: &Path, program: &str, args: &[&str]) -> Result<bool> {
+        let status = Self::command(dir, program)
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .with_context(|| format!("failed to run {}", format_command(program, args)))?;
+        Ok(status.success())
+    }
+
+    fn write(&self, dir: &Path, program: &str, args: &[&str], output: Output) -> Result<()> {
+        match output {
+            Output::Captured => self.read(dir, program, args).map(|_| ()),
+            Output::Shown => {
+                println!("{}", format_command(program, args));
+                Self::run_inheriting(dir, program, args)
+            }
+        }
+    }
+
+    fn interactive(&self, dir: &Path, program: &str, args: &[&str]) -> Result<()> {
+        Self::run_inheriting(dir, program, args)
+    }
+}
+
+/// Lets reads and interactive viewers through to `inner`, but records and prints
+/// mutating commands instead of running them.
+pub struct RecordingBackend {
+    inner: SharedBackend,
+    commands: Mutex<Vec<String>>,
+    quiet: bool,
+}
+
+impl RecordingBackend {
+    pub fn new(inner: SharedBackend) -> Self {
+        Self {
+            inner,
+            commands: Mutex::new(Vec::new()),
+            quiet: false,
+        }
+    }
+
+    /// Records the commands without printing them, for output that is read by tools.
+    pub fn quiet(mut self) -> Self {
+        self.quiet = true;
+        self
+    }
+
+    #[cfg(test)]
+    pub fn commands(&self) -> Vec<String> {
+        self.commands.lock().expect("lock poisoned").clone()
+    }
+
+    /// The commands recorded since the last call.
+    pub fn take_commands(&self) -> Vec<String> {
+        std::mem::take(&mut *self.commands.lock().expect("lock poisoned"))
+    }
+}
+
+impl GitBackend for RecordingBackend {
+    fn read(&self, dir: &Path, program: &str, args: &[&str]) -> Result<String> {
+        self.inner.read(dir, program, args)
+    }
+
+    fn check(&self, dir: &Path, program: &str, args: &[&str]) -> Result<bool> {
+        self.inner.check(dir, program, args)
+    }
+
+    fn write(&self, _dir: &Path, program: &str, args: &[&str], _output: Output) -> Result<()> {
+        let command = format_command(program, args);
+        if !self.quiet {
+            println!("[DRY RUN] {command}");
+        }
+        self.commands.lock().expect("lock poisoned").push(command);
+        Ok(())
+    }
+
+    fn interactive(&self, dir: &Path, program: &str, args: &[&str]) -> Result<()> {
+        self.write(dir, program, args, Output::Shown)
+    }
+}
+
+/// Passes every command through to `inner`, noting down the mutating ones so that
+/// they can be recorded in the journal.
+pub struct LoggingBackend {
+    inner: SharedBackend,
+    commands: Mutex<Vec<String>>,
+}
+
+impl LoggingBackend {
+    pub fn new(inner: SharedBackend) -> Self {
+        Self {
+            inner,
+            commands: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn commands(&self) -> Vec<String> {
+        self.commands.lock().expect("lock poisoned").clone()
+    }
+}
+
+impl GitBackend for LoggingBackend {
+    fn read(&self, dir: &Path, program: &str, args: &[&str]) -> Result<String> {
+        self.inner.read(dir, program, args)
+    }
+
+    fn check(&self, dir: &Path, program: &str, args: &[&str]) -> Result<bool> {
+        self.inner.check(dir, program, args)
+    }
+
+    fn write(&self, dir: &Path, program: &str, args: &[&str], output: Output) -> Result<()> {
+        self.commands
+            .lock()
+            .expect("lock poisoned")
+            .push(format_command(program, args));
+        self.inner.write(dir, program, args, output)
+    }
+
+    fn interactive(&self, dir: &Path, program: &str, args: &[&str]) -> Result<()> {
+        self.inner.interactive(dir, program, args)
+    }
+}
+
+/// An in-memory backend for tests: reads are answered from scripted responses
+/// matched by command-line prefix, and every command is recorded.
+#[cfg(test)]
+#[derive(Default)]
+pub struct FakeBackend {
+    responses: Mutex<Vec<(String, Result<String, String>)>>,
+    invocations: Mutex<Vec<String>>,
+}
+
+#[cfg(test)]
+impl FakeBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers commands starting with `prefix` with `output`.
+    pub fn respond(&self, prefix: &str, output: &str) -> &Self {
+        self.responses
+            .lock()
+            .expect("lock poisoned")
+            .push((prefix.to_string(), Ok(output.to_string())));
+        self
+    }
+
+    /// Makes commands starting with `prefix` fail.
+    pub fn fail(&self, prefix: &str) -> &Self {
+        self.responses
+            .lock()
+            .expect("lock poisoned")
+            .push((prefix.to_string(), Err(format!("{prefix} failed"))));
+        self
+    }
+
+    pub fn invocations(&self) -> Vec<String> {
+        self.invocations.lock().expect("lock poisoned").clone()
+    }
+
+    fn answer(&self, program: &str, args: &[&str]) -> Option<Result<String, String>> {
+        let command = format_command(program, args);
+        self.invocations
+            .lock()
+            .expect("lock poisoned")
+            .push(command.clone());
+        self.responses
+            .lock()
+            .expect("lock poisoned")
+            .iter()
+            .find(|(prefix, _)| command.starts_with(prefix.as_str()))
+            .map(|(_, response)| response.clone())
+    }
+}
+
+#[cfg(test)]
+impl GitBackend for FakeBackend {
+    fn read(&self, _dir: &Path, program: &str, args: &[&str]) -> Result<String> {
+        match self.answer(program, args) {
+            Some(Ok(output)) => Ok(output),
+            Some(Err(message)) => Err(anyhow!(message)),
+            None => Err(anyhow!(
+                "unexpected command: {}",
+                format_command(program, args)
+            )),
+        }
+    }
+
+    fn check(&self, _dir: &Path, program: &str, args: &[&str]) -> Result<bool> {
+        Ok(matches!(self.answer(program, args), Some(Ok(_))))
+    }
+
+    fn write(&self, _dir: &Path, program: &str, args: &[&str], _output: Output) -> Result<()> {
+        match self.answer(program, args) {
+            Some(Err(message)) => Err(anyhow!(message)),
+            _ => Ok(()),
+        }
+    }
+
+    fn interactive(&self, _dir: &Path, program: &str, args: &[&str]) -> Result<()> {
+        self.answer(program, args);
+        Ok(())
+    }
+}
+
+fn format_command(program: &str, args: &[&str]) -> String {
+    let parts: Vec<&str> = std::iter::once(program)
+        .chain(args.iter().copied())
+        .collect();
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recording_backend_passes_reads_through_and_records_writes() -> Result<()> {
+        let fake = Arc::new(FakeBackend::new());
+        fake.respond("git status", " M file.txt\n");
+        let recording = RecordingBackend::new(fake.clone());
+        let dir = Path::new("/tmp/repo");
+
+        assert_eq!(
+            recording.read(dir, "git", &["status", "--porcelain"])?,
+            " M file.txt\n"
+        );
+        recording.write(dir, "git", &["branch", "-D", "feature"], Output::Shown)?;
+        recording.interactive(dir, "tig", &["main"])?;
+
+        assert_eq!(
+            recording.commands(),
+            vec!["git branch -D feature", "tig main"]
+        );
+        assert_eq!(fake.invocations(), vec!["git status --porcelain"]);
+        Ok(())
+    }
+
+    #[test]
+    fn fake_backend_fails_unscripted_reads() {
+        let fake = FakeBackend::new();
+        fake.fail("git config");
+        let dir = Path::new("/tmp/repo");
+        assert!(fake.read(dir, "git", &["config", "--get", "x"]).is_err());
+        assert!(fake.read(dir, "git", &["remote"]).is_err());
+        assert!(!fake.check(dir, "git", &["rev-parse", "HEAD"]).unwrap());
+    }
+}
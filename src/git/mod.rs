@@ -1,36 +1,138 @@
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::sync::{Arc, OnceLock};
 
 use anyhow::{Context, Result, anyhow};
-use serde::Deserialize;
+
+pub mod backend;
+mod repo_config;
+
+use crate::config::{PullRequestsConfig, ToolsConfig};
+use crate::forge::{Forge, ForgeKind, NewPullRequest, PullRequest, PullRequests, remote_owner};
+use backend::{LoggingBackend, Output, ProcessBackend, SharedBackend};
+pub use repo_config::{RepoConfig, SyncStrategy};
 
 #[derive(Debug, Clone)]
 pub struct Branch {
     pub refname: String,
+    /// SHA of the commit the branch points to.
+    pub tip: String,
     pub upstream: Option<Upstream>,
     pub worktree_path: Option<PathBuf>,
+    /// The default branch this branch's changes are already contained in, if any.
+    /// Only filled in by [`GitRepo::detect_merged_branches`].
+    pub merged_into: Option<String>,
+    /// The remote `git push` sends this branch to, honouring `branch.<name>.pushRemote`
+    /// and `remote.pushDefault`.
+    pub push_remote: Option<String>,
+    /// Tracking status against `<push_remote>/<refname>`, set only when the branch is
+    /// pushed somewhere other than where its upstream lives (a triangular workflow).
+    pub push_target: Option<Upstream>,
+    /// Whether the branch matches a protected pattern, so that it must never be deleted
+    /// or rewritten. Only filled in by [`crate::settings::Settings`].
+    pub protected: bool,
+    /// The latest pull request from this branch on the forge, if looked up. Only filled
+    /// in by [`crate::settings::Settings`].
+    pub pull_request: Option<PullRequest>,
 }
 
-#[derive(Debug, Clone)]
+/// A copy of a branch's old tip, kept under [`BACKUP_REF_PREFIX`] before the branch
+/// was deleted or rewritten.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    pub refname: String,
+    pub branch: String,
+    pub tip: String,
+    /// Unix time the backup was taken.
+    pub timestamp: i64,
+}
+
+pub const BACKUP_REF_PREFIX: &str = "refs/branch-assistant/backup/";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Identity {
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+/// Who made and committed the commit a branch points to, and when.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BranchCommitInfo {
     pub commit_timestamp: i64,
     pub commit_date: String,
     pub committer: String,
+    pub committer_email: String,
+    /// Unlike the committer, the author stays the same when a branch is rebased.
+    pub author: String,
+    pub author_email: String,
+    pub author_timestamp: i64,
+    pub author_date: String,
+    pub subject: String,
 }
 
 impl Branch {
     pub fn needs_action(&self) -> bool {
-        self.upstream
-            .as_ref()
-            .map(|upstream| upstream.status != UpstreamStatus::Identical)
-            .unwrap_or(true)
+        match &self.upstream {
+            None => true,
+            Some(upstream) => match upstream.status {
+                UpstreamStatus::Identical => false,
+                // In a triangular workflow a feature branch is expected to be ahead of
+                // the branch it pulls from; it only needs attention if it's not pushed.
+                UpstreamStatus::LocalIsAheadOfUpstream => self
+                    .push_target
+                    .as_ref()
+                    .is_none_or(|push| push.status != UpstreamStatus::Identical),
+                _ => true,
+            },
+        }
+    }
+
+    /// The remote to push to, falling back to `origin` when git couldn't tell.
+    pub fn push_remote(&self) -> &str {
+        self.push_remote.as_deref().unwrap_or("origin")
+    }
+
+    /// The remote the branch's work goes to and the branch's name there: where it is
+    /// pushed in a triangular workflow, or else its upstream, or else its own name on
+    /// its push remote.
+    pub fn remote_branch(&self) -> (&str, &str) {
+        match self.push_target.as_ref().or(self.upstream.as_ref()) {
+            Some(target) => {
+                let name = target
+                    .name
+                    .strip_prefix(target.remote.as_str())
+                    .and_then(|name| name.strip_prefix('/'))
+                    .unwrap_or(&target.name);
+                (&target.remote, name)
+            }
+            None => (self.push_remote(), &self.refname),
+        }
+    }
+
+    /// Whether the branch may hold work that only exists locally, and so is worth
+    /// checking against the default branch before suggesting to delete it.
+    fn may_be_merged(&self) -> bool {
+        match &self.upstream {
+            None => true,
+            Some(upstream) => matches!(
+                upstream.status,
+                UpstreamStatus::LocalIsAheadOfUpstream
+                    | UpstreamStatus::MergeNeeded
+                    | UpstreamStatus::UpstreamIsGone
+            ),
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Upstream {
     pub name: String,
+    pub remote: String,
     pub status: UpstreamStatus,
+    /// Number of commits on the local branch that are not on the upstream.
+    pub ahead: u32,
+    /// Number of commits on the upstream that are not on the local branch.
+    pub behind: u32,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -42,76 +144,291 @@ pub enum UpstreamStatus {
     UpstreamIsGone,
 }
 
+/// A commit's message, split into its subject line and the rest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitMessage {
+    pub subject: String,
+    pub body: String,
+}
+
+/// How many of the default branch's most recent commits a squash merge is looked
+/// for among.
+const SQUASH_MERGE_SEARCH_LIMIT: &str = "1000";
+
+/// Branch names tried, in order, when nothing else tells us the default branch.
+const WELL_KNOWN_DEFAULT_BRANCHES: &[&str] = &["main", "master", "trunk", "develop"];
+
 pub struct GitRepo {
     dir: PathBuf,
+    backend: SharedBackend,
+    tools: ToolsConfig,
+    pull_request_defaults: PullRequestsConfig,
+    config: OnceLock<RepoConfig>,
+    default_branch: OnceLock<Option<String>>,
 }
 
 impl GitRepo {
     pub fn new(dir: PathBuf) -> Self {
-        Self { dir }
+        Self::with_backend(dir, ProcessBackend::shared())
+    }
+
+    pub fn with_backend(dir: PathBuf, backend: SharedBackend) -> Self {
+        Self {
+            dir,
+            backend,
+            tools: ToolsConfig::default(),
+            pull_request_defaults: PullRequestsConfig::default(),
+            config: OnceLock::new(),
+            default_branch: OnceLock::new(),
+        }
+    }
+
+    /// Uses `tools` instead of plain `tig` and `gh`.
+    pub fn with_tools(mut self, tools: ToolsConfig) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    /// Opens new pull requests with these reviewers, labels and draft setting, along
+    /// with the repository's own reviewers and labels.
+    pub fn with_pull_request_defaults(mut self, defaults: PullRequestsConfig) -> Self {
+        self.pull_request_defaults = defaults;
+        self
     }
 
     pub fn dir(&self) -> &Path {
         &self.dir
     }
 
+    /// Runs `f` against this repository, also returning the mutating commands it ran.
+    pub fn logging_writes<T>(&self, f: impl FnOnce(&GitRepo) -> T) -> (T, Vec<String>) {
+        let logger = Arc::new(LoggingBackend::new(self.backend.clone()));
+        let repo = GitRepo {
+            dir: self.dir.clone(),
+            backend: logger.clone(),
+            tools: self.tools.clone(),
+            pull_request_defaults: self.pull_request_defaults.clone(),
+            config: self.config.clone(),
+            default_branch: self.default_branch.clone(),
+        };
+        let result = f(&repo);
+        (result, logger.commands())
+    }
+
     pub fn get_branches(&self) -> Result<Vec<Branch>> {
         let output = self.run_and_capture(
             "git",
             &[
                 "for-each-ref",
-                "--format=%(refname:short)|%(upstream:short)|%(upstream:track)|%(worktreepath)",
+                "--format=%(refname:short)|%(objectname)|%(upstream:short)|%(upstream:track)|%(upstream:remotename)|%(push:remotename)|%(worktreepath)",
                 "refs/heads/",
             ],
         )?;
-        parse_branches(&output)
+        let mut branches = parse_branches(&output)?;
+        for branch in branches.iter_mut() {
+            branch.push_target = self.push_target(branch)?;
+        }
+        Ok(branches)
+    }
+
+    fn push_target(&self, branch: &Branch) -> Result<Option<Upstream>> {
+        let (Some(upstream), Some(push_remote)) = (&branch.upstream, &branch.push_remote) else {
+            return Ok(None);
+        };
+        if upstream.remote == *push_remote {
+            return Ok(None);
+        }
+        let name = format!("{push_remote}/{}", branch.refname);
+        let remote_ref = format!("refs/remotes/{name}");
+        if !self.run_succeeds("git", &["rev-parse", "--verify", "--quiet", &remote_ref])? {
+            return Ok(Some(Upstream {
+                name,
+                remote: push_remote.clone(),
+                status: UpstreamStatus::UpstreamIsGone,
+                ahead: 0,
+                behind: 0,
+            }));
+        }
+        let range = format!("{}...{remote_ref}", branch.refname);
+        let counts =
+            self.run_and_capture("git", &["rev-list", "--left-right", "--count", &range])?;
+        let mut parts = counts.split_whitespace().map(|n| n.parse::<u32>());
+        let (Some(Ok(ahead)), Some(Ok(behind))) = (parts.next(), parts.next()) else {
+            return Err(anyhow!("unexpected output from git rev-list: {counts}"));
+        };
+        Ok(Some(Upstream {
+            name,
+            remote: push_remote.clone(),
+            status: status_from_counts(ahead > 0, behind > 0),
+            ahead,
+            behind,
+        }))
     }
 
     pub fn branch_commit_infos(
         &self,
     ) -> Result<std::collections::HashMap<String, BranchCommitInfo>> {
+        // Fields are separated by the unit separator, which can't appear in a subject.
         let output = self.run_and_capture(
             "git",
             &[
                 "for-each-ref",
-                "--format=%(refname:short)|%(committerdate:unix)|%(committerdate:short)|%(committername)",
+                "--format=%(refname:short)%1f%(committerdate:unix)%1f%(committerdate:short)%1f%(committername)%1f%(committeremail)%1f%(authorname)%1f%(authoremail)%1f%(authordate:unix)%1f%(authordate:short)%1f%(contents:subject)",
                 "refs/heads/",
             ],
         )?;
 
         let mut map = std::collections::HashMap::new();
         for line in output.lines().filter(|line| !line.trim().is_empty()) {
-            let parts: Vec<&str> = line.splitn(4, '|').collect();
-            if parts.len() != 4 {
+            let parts: Vec<&str> = line.split('\x1f').collect();
+            if parts.len() != 10 {
                 return Err(anyhow!("unexpected output from git for-each-ref: {line}"));
             }
-            let timestamp: i64 = parts[1]
-                .parse()
-                .with_context(|| format!("failed to parse committer timestamp: {}", parts[1]))?;
+            let timestamp = |text: &str| -> Result<i64> {
+                text.parse()
+                    .with_context(|| format!("failed to parse commit timestamp: {text}"))
+            };
+            let email = |text: &str| {
+                text.trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            };
             map.insert(
                 parts[0].to_string(),
                 BranchCommitInfo {
-                    commit_timestamp: timestamp,
+                    commit_timestamp: timestamp(parts[1])?,
                     commit_date: parts[2].to_string(),
                     committer: parts[3].to_string(),
+                    committer_email: email(parts[4]),
+                    author: parts[5].to_string(),
+                    author_email: email(parts[6]),
+                    author_timestamp: timestamp(parts[7])?,
+                    author_date: parts[8].to_string(),
+                    subject: parts[9].to_string(),
                 },
             );
         }
         Ok(map)
     }
 
-    pub fn push(&self, refname: &str) -> Result<()> {
-        self.run_interactive_printing("git", &["push", "origin", refname])
+    /// Fills in [`Branch::merged_into`] for branches whose changes are already part of
+    /// the default branch, whether it was merged, rebased or squashed in.
+    pub fn detect_merged_branches(&self, branches: &mut [Branch]) {
+        let Ok(default_branch) = self.default_branch() else {
+            return;
+        };
+        let Some(target) = self.merge_target(&default_branch) else {
+            return;
+        };
+        let mut target_patch_ids = None;
+        for branch in branches
+            .iter_mut()
+            .filter(|branch| branch.refname != default_branch && branch.may_be_merged())
+        {
+            if self
+                .is_merged_into(&branch.refname, &target, &mut target_patch_ids)
+                .unwrap_or(false)
+            {
+                branch.merged_into = Some(default_branch.clone());
+            }
+        }
+    }
+
+    /// Checks whether `branch` is contained in `target`: either as an ancestor, or
+    /// with every commit (or the squash of all of them) present as an equivalent patch.
+    /// A branch that hasn't moved since it was created has no commits of its own and
+    /// doesn't count. The patch ids of `target`'s recent commits are kept in
+    /// `target_patch_ids` once they are needed, for checking the next branch.
+    pub fn is_merged_into(
+        &self,
+        branch: &str,
+        target: &str,
+        target_patch_ids: &mut Option<HashSet<String>>,
+    ) -> Result<bool> {
+        if self.run_succeeds("git", &["merge-base", "--is-ancestor", branch, target])? {
+            let tip = self.run_and_capture("git", &["rev-parse", branch])?;
+            return Ok(self
+                .creation_point(branch)
+                .is_none_or(|created| created != tip.trim()));
+        }
+        let cherry = self.run_and_capture("git", &["cherry", target, branch])?;
+        if all_commits_applied(&cherry) {
+            return Ok(true);
+        }
+
+        let merge_base = self.run_and_capture("git", &["merge-base", target, branch])?;
+        let squashed = self.patch_ids(
+            "git diff --no-color --no-ext-diff \"$1\" \"$2\"",
+            &[merge_base.trim(), branch],
+        )?;
+        let Some(squashed) = squashed.into_iter().next() else {
+            return Ok(false);
+        };
+        let target_patch_ids = match target_patch_ids {
+            Some(ids) => ids,
+            None => target_patch_ids.insert(self.patch_ids(
+                "git log -p --no-merges --no-color --no-ext-diff --max-count=\"$1\" \"$2\"",
+                &[SQUASH_MERGE_SEARCH_LIMIT, target],
+            )?),
+        };
+        Ok(target_patch_ids.contains(&squashed))
     }
 
-    pub fn push_creating_origin(&self, refname: &str) -> Result<()> {
-        self.run_interactive_printing("git", &["push", "--set-upstream", "origin", refname])
+    /// Where `branch` pointed when it was created, going by its reflog, or `None` if
+    /// that isn't known any more.
+    fn creation_point(&self, branch: &str) -> Option<String> {
+        let refname = format!("refs/heads/{branch}");
+        let reflog = self
+            .run_and_capture("git", &["reflog", "show", "--format=%H", &refname])
+            .ok()?;
+        reflog
+            .lines()
+            .last()
+            .map(|commit| commit.trim().to_string())
+    }
+
+    /// The stable patch ids of what `command` prints, a `git diff` or `git log -p`
+    /// given `args` as `$1`, `$2` and so on. `git patch-id` only reads its input, so
+    /// this goes through `sh`.
+    fn patch_ids(&self, command: &str, args: &[&str]) -> Result<HashSet<String>> {
+        let script = format!("{command} | git patch-id --stable");
+        let sh_args: Vec<&str> = ["-c", script.as_str(), "sh"]
+            .into_iter()
+            .chain(args.iter().copied())
+            .collect();
+        let output = self.run_and_capture("sh", &sh_args)?;
+        Ok(output
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .map(str::to_string)
+            .collect())
+    }
+
+    pub fn fetch_and_prune(&self) -> Result<()> {
+        // Fetching only refreshes remote-tracking refs, so it's treated as a read and
+        // still happens in dry runs.
+        self.run_and_capture("git", &["fetch", "--all", "--prune", "--quiet"])?;
+        Ok(())
+    }
+
+    pub fn push(&self, refname: &str, remote: &str) -> Result<()> {
+        self.run_interactive_printing("git", &["push", remote, refname])
+    }
+
+    pub fn push_creating_origin(&self, refname: &str, remote: &str) -> Result<()> {
+        self.run_interactive_printing("git", &["push", "--set-upstream", remote, refname])
     }
 
     pub fn rebase(&self, refname: &str, upstream: &str) -> Result<()> {
         self.run_interactive_printing("git", &["rebase", upstream, refname])
     }
 
+    /// Merges `upstream` into `refname`, which is checked out to do so.
+    pub fn merge(&self, refname: &str, upstream: &str) -> Result<()> {
+        self.checkout_branch(refname)?;
+        self.run_interactive_printing("git", &["merge", "--no-edit", upstream])
+    }
+
     pub fn delete_branch_forcefully(&self, branch: &str) -> Result<()> {
         self.run_interactive_printing("git", &["branch", "-D", branch])
     }
@@ -122,169 +439,490 @@ impl GitRepo {
         self.run_interactive_printing("git", &args)
     }
 
-    pub fn create_pull_request(&self, refname: &str) -> Result<()> {
-        let default_branch = self.default_branch()?;
-        self.run_interactive_printing(
-            "gh",
-            &["pr", "create", "--head", refname, "--base", &default_branch],
-        )
+    /// Points a new ref under [`BACKUP_REF_PREFIX`] at `tip` and returns its name.
+    pub fn create_backup_ref(&self, branch: &str, tip: &str, timestamp: i64) -> Result<String> {
+        let short_tip = &tip[..tip.len().min(7)];
+        let refname = format!("{BACKUP_REF_PREFIX}{branch}/{timestamp}-{short_tip}");
+        self.run_quietly("git", &["update-ref", &refname, tip])?;
+        Ok(refname)
+    }
+
+    /// All backups in the repository, newest first.
+    pub fn backups(&self) -> Result<Vec<Backup>> {
+        let output = self.run_and_capture(
+            "git",
+            &[
+                "for-each-ref",
+                "--format=%(refname)|%(objectname)",
+                BACKUP_REF_PREFIX,
+            ],
+        )?;
+        let mut backups: Vec<Backup> = output.lines().filter_map(parse_backup).collect();
+        backups.sort_by_key(|backup| std::cmp::Reverse(backup.timestamp));
+        Ok(backups)
+    }
+
+    /// The commit a local branch points to, or `None` if there is no such branch.
+    pub fn branch_tip(&self, branch: &str) -> Result<Option<String>> {
+        let refname = format!("refs/heads/{branch}");
+        if !self.ref_exists(&refname) {
+            return Ok(None);
+        }
+        let tip = self.run_and_capture("git", &["rev-parse", &refname])?;
+        Ok(Some(tip.trim().to_string()))
+    }
+
+    /// Creates `branch` at `tip`, or moves it there if it exists. Git refuses to move
+    /// a branch that is checked out.
+    pub fn restore_branch(&self, branch: &str, tip: &str) -> Result<()> {
+        self.run_interactive_printing("git", &["branch", "--force", branch, tip])
+    }
+
+    /// Opens a pull request from `refname`, as pushed to `remote`, into `base`, unless
+    /// one is already open. It is titled and described after the commits between the
+    /// two.
+    pub fn create_pull_request(&self, refname: &str, remote: &str, base: &str) -> Result<()> {
+        let forge = self.forge();
+        if let Ok(Some(pull)) = self.pull_request_for_branch(remote, refname)
+            && pull.state.is_active()
+        {
+            eprintln!(
+                "{refname} already has pull request #{}: {}",
+                pull.number, pull.url
+            );
+            return Ok(());
+        }
+        let base_ref = self.merge_target(base).unwrap_or_else(|| base.to_string());
+        let commits = self.commit_messages(&base_ref, refname)?;
+        let defaults = &self.pull_request_defaults;
+        let pull = NewPullRequest {
+            draft: defaults.draft,
+            reviewers: [&defaults.reviewers[..], &self.config().reviewers[..]].concat(),
+            labels: [&defaults.labels[..], &self.config().labels[..]].concat(),
+            ..NewPullRequest::new(refname, base, &commits)
+        };
+        forge.create_pull_request(self, &pull)
+    }
+
+    /// The most recent pull request from `branch` on `remote`, in the repository or
+    /// fork the remote's URL belongs to. A pull request from someone else's fork that
+    /// happens to use the same branch name isn't it.
+    pub fn pull_request_for_branch(
+        &self,
+        remote: &str,
+        branch: &str,
+    ) -> Result<Option<PullRequest>> {
+        let urls = self.remote_urls();
+        let owner = urls
+            .get(remote)
+            .and_then(|url| remote_owner(url))
+            .ok_or_else(|| anyhow!("cannot tell who owns remote {remote}"))?;
+        self.forge().pull_request_for_branch(self, owner, branch)
+    }
+
+    pub fn open_pull_request(&self, number: u64) -> Result<()> {
+        self.forge().open_pull_request(self, number)
+    }
+
+    /// The messages of the commits on `refname` that aren't on `base`, oldest first.
+    pub fn commit_messages(&self, base: &str, refname: &str) -> Result<Vec<CommitMessage>> {
+        let range = format!("{base}..{refname}");
+        let output = self.run_and_capture(
+            "git",
+            &["log", "--reverse", "--format=%s%x1f%b%x1e", &range],
+        )?;
+        Ok(output
+            .split('\x1e')
+            .map(str::trim)
+            .filter(|record| !record.is_empty())
+            .map(|record| {
+                let (subject, body) = record.split_once('\x1f').unwrap_or((record, ""));
+                CommitMessage {
+                    subject: subject.trim().to_string(),
+                    body: body.trim().to_string(),
+                }
+            })
+            .collect())
+    }
+
+    /// The local branch `refname` was started from, when that isn't the default branch
+    /// and hasn't been merged into it yet: the most recently committed to of the
+    /// branches whose tip is behind `refname`'s.
+    pub fn stack_parent(&self, refname: &str) -> Option<String> {
+        let default_branch = self.default_branch().ok()?;
+        let target = self.merge_target(&default_branch)?;
+        let tip = self.branch_tip(refname).ok()??;
+        let output = self
+            .run_and_capture(
+                "git",
+                &[
+                    "for-each-ref",
+                    "--format=%(objectname) %(refname:short)",
+                    "--sort=-committerdate",
+                    "--merged",
+                    &tip,
+                    "--no-merged",
+                    &target,
+                    "refs/heads/",
+                ],
+            )
+            .ok()?;
+        output
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .find(|(parent_tip, name)| {
+                *parent_tip != tip && *name != refname && *name != default_branch
+            })
+            .map(|(_, name)| name.to_string())
+    }
+
+    /// The pull requests from this repository's branches, or none if it has no remote to
+    /// have them on.
+    pub fn pull_requests(&self) -> Result<PullRequests> {
+        if self.remotes().is_empty() {
+            return Ok(PullRequests::new());
+        }
+        self.forge().pull_requests(self)
+    }
+
+    /// The forge the repository is hosted on, from its `branch-assistant.forge` git
+    /// config or else guessed from its remote URL.
+    pub fn forge(&self) -> Box<dyn Forge> {
+        let kind = self.config().forge.unwrap_or_else(|| {
+            self.remote_url()
+                .map(|url| ForgeKind::from_remote_url(&url))
+                .unwrap_or(ForgeKind::GitHub)
+        });
+        kind.forge(&self.tools)
+    }
+
+    /// Runs a read-only forge command, given as a configured command line.
+    pub fn read_tool(&self, command: &str, args: &[&str]) -> Result<String> {
+        let (program, args) = tool_command(command, args);
+        self.run_and_capture(program, &args)
+    }
+
+    /// Runs a forge command that changes something, showing its output.
+    pub fn run_tool(&self, command: &str, args: &[&str]) -> Result<()> {
+        let (program, args) = tool_command(command, args);
+        self.run_interactive_printing(program, &args)
+    }
+
+    /// Runs a user-defined command line with `sh -c`, showing its output.
+    pub fn run_shell(&self, command: &str) -> Result<()> {
+        self.run_interactive_printing("sh", &["-c", command])
     }
 
     pub fn show_log(&self, branch: &str) -> Result<()> {
-        self.run_interactive("tig", &[branch])
+        let (program, args) = tool_command(&self.tools.log, &[branch]);
+        self.run_interactive(program, &args)
+    }
+
+    /// The checked out branch, or `None` on a detached `HEAD`.
+    pub fn current_branch(&self) -> Result<Option<String>> {
+        let output = self.run_and_capture("git", &["branch", "--show-current"])?;
+        let branch = output.trim();
+        Ok((!branch.is_empty()).then(|| branch.to_string()))
     }
 
     pub fn checkout_branch(&self, branch: &str) -> Result<()> {
-        self.run_and_capture("git", &["checkout", branch])?;
-        Ok(())
+        self.run_quietly("git", &["checkout", branch])
     }
 
     pub fn checkout_default_branch(&self) -> Result<()> {
-        let branch = self.init_default_branch()?;
+        let branch = self.default_branch()?;
         self.checkout_branch(&branch)
             .with_context(|| format!("failed to checkout default branch '{}'", branch))
     }
 
+    /// Fetches all remotes, reporting a failure on stderr instead of returning it so
+    /// that one unreachable remote doesn't stop the analysis of the others.
+    pub fn fetch_and_prune_reporting_errors(&self) {
+        if let Err(err) = self.fetch_and_prune() {
+            eprintln!("Failed to fetch {}: {err:#}", self.dir.display());
+        }
+    }
+
+    /// The top level of the working tree the directory belongs to, which is an error
+    /// if it isn't in one or git refuses to work in it.
+    pub fn toplevel(&self) -> Result<PathBuf> {
+        let output = self.run_and_capture("git", &["rev-parse", "--show-toplevel"])?;
+        Ok(PathBuf::from(output.trim()))
+    }
+
     pub fn is_dirty(&self) -> Result<bool> {
         let output = self.run_and_capture("git", &["status", "--porcelain"])?;
         Ok(!output.trim().is_empty())
     }
 
-    fn default_branch(&self) -> Result<String> {
-        let output = self.run_and_capture("gh", &["repo", "view", "--json", "defaultBranchRef"])?;
-        let response: DefaultBranchResponse =
-            serde_json::from_str(&output).context("failed to parse gh repo view output")?;
-        Ok(response.default_branch_ref.name.trim().to_string())
+    /// Resolves this clone's default branch: from the remotes' `HEAD`, then the
+    /// `branch-assistant.defaultBranch` git config, then the forge, and finally
+    /// well-known branch names. The result is remembered for the lifetime of `self`.
+    pub fn default_branch(&self) -> Result<String> {
+        self.default_branch
+            .get_or_init(|| self.resolve_default_branch())
+            .clone()
+            .ok_or_else(|| {
+                anyhow!(
+                    "could not determine the default branch of {}",
+                    self.dir.display()
+                )
+            })
     }
 
-    fn init_default_branch(&self) -> Result<String> {
-        match self.run_and_capture("git", &["config", "--get", "init.defaultBranch"]) {
-            Ok(branch) => {
-                let branch = branch.trim();
-                if !branch.is_empty() {
-                    return Ok(branch.to_string());
-                }
-                Ok("main".to_string())
-            }
-            Err(_) => Ok("main".to_string()),
+    fn resolve_default_branch(&self) -> Option<String> {
+        self.remote_head_branch()
+            .or_else(|| self.config().default_branch.clone())
+            .or_else(|| self.forge_default_branch().ok())
+            .or_else(|| self.well_known_default_branch())
+    }
+
+    fn remote_head_branch(&self) -> Option<String> {
+        self.remotes().into_iter().find_map(|remote| {
+            let head = format!("refs/remotes/{remote}/HEAD");
+            let target = self
+                .run_and_capture("git", &["symbolic-ref", "--short", &head])
+                .ok()?;
+            target
+                .trim()
+                .strip_prefix(&format!("{remote}/"))
+                .map(str::to_string)
+        })
+    }
+
+    fn forge_default_branch(&self) -> Result<String> {
+        if self.remotes().is_empty() {
+            return Err(anyhow!("repository has no remotes"));
         }
+        self.forge().default_branch(self)
     }
 
-    fn run_and_capture(&self, program: &str, args: &[&str]) -> Result<String> {
-        let output = self
-            .command(program)
-            .args(args)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
-            .with_context(|| format!("failed to run {}", format_command(program, args)))?;
-
-        if output.status.success() {
-            Ok(String::from_utf8(output.stdout).context("command output was not valid UTF-8")?)
-        } else {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            Err(anyhow!(
-                "{} failed (stdout: {}, stderr: {})",
-                format_command(program, args),
-                stdout.trim(),
-                stderr.trim()
-            ))
+    fn well_known_default_branch(&self) -> Option<String> {
+        let remotes = self.remotes();
+        WELL_KNOWN_DEFAULT_BRANCHES
+            .iter()
+            .find(|name| {
+                self.ref_exists(&format!("refs/heads/{name}"))
+                    || remotes
+                        .iter()
+                        .any(|remote| self.ref_exists(&format!("refs/remotes/{remote}/{name}")))
+            })
+            .map(|name| name.to_string())
+    }
+
+    /// The URL of the repository's main remote: `origin`, or else the first one.
+    pub fn remote_url(&self) -> Option<String> {
+        let remote = self.remotes().into_iter().next()?;
+        self.run_and_capture("git", &["remote", "get-url", &remote])
+            .ok()
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty())
+    }
+
+    /// The URL of every remote, by name.
+    pub fn remote_urls(&self) -> HashMap<String, String> {
+        self.run_and_capture("git", &["config", "--get-regexp", r"^remote\..*\.url$"])
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let (key, url) = line.split_once(' ')?;
+                let remote = key.strip_prefix("remote.")?.strip_suffix(".url")?;
+                Some((remote.to_string(), url.trim().to_string()))
+            })
+            .collect()
+    }
+
+    /// Clones `url` into `dest`, relative to this directory.
+    pub fn clone_repository(&self, url: &str, dest: &Path) -> Result<()> {
+        let dest = dest.to_string_lossy();
+        self.run_interactive_printing("git", &["clone", url, &dest])
+    }
+
+    /// Who commits in this repository, from `user.name` and `user.email`.
+    pub fn identity(&self) -> Identity {
+        let value = |key: &str| {
+            self.run_and_capture("git", &["config", "--get", key])
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        Identity {
+            name: value("user.name"),
+            email: value("user.email"),
         }
     }
 
-    fn run_interactive(&self, program: &str, args: &[&str]) -> Result<()> {
-        let status = self
-            .command(program)
-            .args(args)
-            .status()
-            .with_context(|| format!("failed to run {}", format_command(program, args)))?;
-
-        if status.success() {
-            Ok(())
-        } else {
-            Err(anyhow!(
-                "{} exited with status {}",
-                format_command(program, args),
-                status
-            ))
+    pub fn set_config(&self, key: &str, value: &str) -> Result<()> {
+        self.run_quietly("git", &["config", key, value])
+    }
+
+    /// Remote names, with `origin` first if it exists.
+    fn remotes(&self) -> Vec<String> {
+        let mut remotes: Vec<String> = self
+            .run_and_capture("git", &["remote"])
+            .map(|output| output.lines().map(|line| line.trim().to_string()).collect())
+            .unwrap_or_default();
+        remotes.retain(|remote| !remote.is_empty());
+        remotes.sort_by_key(|remote| remote != "origin");
+        remotes
+    }
+
+    /// This repository's `branch-assistant.*` git config, read once for the lifetime of
+    /// `self`.
+    pub fn config(&self) -> &RepoConfig {
+        self.config.get_or_init(|| {
+            let pattern = format!("^{}\\.", repo_config::SECTION);
+            self.run_and_capture("git", &["config", "--get-regexp", &pattern])
+                .map(|output| RepoConfig::parse(&output))
+                .unwrap_or_default()
+        })
+    }
+
+    fn ref_exists(&self, refname: &str) -> bool {
+        self.run_succeeds("git", &["rev-parse", "--verify", "--quiet", refname])
+            .unwrap_or(false)
+    }
+
+    /// The ref merged branches end up in: the default branch's upstream if it has
+    /// one, otherwise the local default branch, otherwise a remote's copy of it.
+    fn merge_target(&self, default_branch: &str) -> Option<String> {
+        let upstream_spec = format!("{default_branch}@{{upstream}}");
+        if let Ok(upstream) =
+            self.run_and_capture("git", &["rev-parse", "--abbrev-ref", &upstream_spec])
+        {
+            return Some(upstream.trim().to_string());
         }
+        if self.ref_exists(&format!("refs/heads/{default_branch}")) {
+            return Some(default_branch.to_string());
+        }
+        self.remotes()
+            .into_iter()
+            .map(|remote| format!("{remote}/{default_branch}"))
+            .find(|name| self.ref_exists(&format!("refs/remotes/{name}")))
     }
 
-    fn run_interactive_printing(&self, program: &str, args: &[&str]) -> Result<()> {
-        println!("{}", format_command(program, args));
-        self.run_interactive(program, args)
+    fn run_succeeds(&self, program: &str, args: &[&str]) -> Result<bool> {
+        self.backend.check(&self.dir, program, args)
     }
 
-    fn command(&self, program: &str) -> Command {
-        let mut command = Command::new(program);
-        command.current_dir(&self.dir);
-        command
+    fn run_and_capture(&self, program: &str, args: &[&str]) -> Result<String> {
+        self.backend.read(&self.dir, program, args)
+    }
+
+    fn run_quietly(&self, program: &str, args: &[&str]) -> Result<()> {
+        self.backend
+            .write(&self.dir, program, args, Output::Captured)
+    }
+
+    fn run_interactive(&self, program: &str, args: &[&str]) -> Result<()> {
+        self.backend.interactive(&self.dir, program, args)
     }
-}
 
-#[derive(Deserialize)]
-struct DefaultBranchResponse {
-    #[serde(rename = "defaultBranchRef")]
-    default_branch_ref: DefaultBranchRef,
+    fn run_interactive_printing(&self, program: &str, args: &[&str]) -> Result<()> {
+        self.backend.write(&self.dir, program, args, Output::Shown)
+    }
 }
 
-#[derive(Deserialize)]
-struct DefaultBranchRef {
-    name: String,
+/// Splits a configured tool command line such as `tig --all` into the program and its
+/// arguments, followed by `args`.
+fn tool_command<'a>(command: &'a str, args: &[&'a str]) -> (&'a str, Vec<&'a str>) {
+    let mut words = command.split_whitespace();
+    let program = words.next().unwrap_or(command);
+    (program, words.chain(args.iter().copied()).collect())
 }
 
-fn format_command(program: &str, args: &[&str]) -> String {
-    let parts: Vec<&str> = std::iter::once(program)
-        .chain(args.iter().copied())
-        .collect();
-    parts.join(" ")
+fn parse_backup(line: &str) -> Option<Backup> {
+    let (refname, tip) = line.split_once('|')?;
+    let (branch, stamp) = refname.strip_prefix(BACKUP_REF_PREFIX)?.rsplit_once('/')?;
+    let (timestamp, _) = stamp.split_once('-')?;
+    Some(Backup {
+        refname: refname.to_string(),
+        branch: branch.to_string(),
+        tip: tip.to_string(),
+        timestamp: timestamp.parse().ok()?,
+    })
 }
 
 fn parse_branches(output: &str) -> Result<Vec<Branch>> {
     let mut branches = Vec::new();
     for line in output.lines().filter(|line| !line.trim().is_empty()) {
-        let parts: Vec<&str> = line.splitn(4, '|').collect();
-        if parts.len() != 4 {
+        let parts: Vec<&str> = line.splitn(7, '|').collect();
+        if parts.len() != 7 {
             return Err(anyhow!("unexpected output from git for-each-ref: {line}"));
         }
         let refname = parts[0].trim().to_string();
-        let upstream_name = parts[1].trim();
-        let track = parts[2].trim();
-        let worktree_path = match parts[3].trim() {
+        let tip = parts[1].trim().to_string();
+        let upstream_name = parts[2].trim();
+        let track = parts[3].trim();
+        let upstream_remote = parts[4].trim();
+        let push_remote = match parts[5].trim() {
+            "" => None,
+            remote => Some(remote.to_string()),
+        };
+        let worktree_path = match parts[6].trim() {
             "" => None,
             path => Some(PathBuf::from(path)),
         };
         let upstream = if upstream_name.is_empty() {
             None
         } else {
+            let (status, ahead, behind) = parse_upstream_track(track);
             Some(Upstream {
                 name: upstream_name.to_string(),
-                status: parse_upstream_track(track),
+                remote: upstream_remote.to_string(),
+                status,
+                ahead,
+                behind,
             })
         };
         branches.push(Branch {
             refname,
+            tip,
             upstream,
             worktree_path,
+            merged_into: None,
+            push_remote,
+            push_target: None,
+            protected: false,
+            pull_request: None,
         });
     }
     Ok(branches)
 }
 
-fn parse_upstream_track(track: &str) -> UpstreamStatus {
+/// `git cherry` marks commits with an equivalent change upstream with `-`.
+fn all_commits_applied(cherry_output: &str) -> bool {
+    cherry_output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .all(|line| line.starts_with('-'))
+}
+
+fn parse_upstream_track(track: &str) -> (UpstreamStatus, u32, u32) {
     if track.is_empty() {
-        return UpstreamStatus::Identical;
+        return (UpstreamStatus::Identical, 0, 0);
     }
     let inner = track
         .strip_prefix('[')
         .and_then(|s| s.strip_suffix(']'))
         .unwrap_or(track);
     if inner == "gone" {
-        return UpstreamStatus::UpstreamIsGone;
+        return (UpstreamStatus::UpstreamIsGone, 0, 0);
     }
-    let has_ahead = inner.split(',').any(|part| part.trim().starts_with("ahead"));
-    let has_behind = inner.split(',').any(|part| part.trim().starts_with("behind"));
+    let count = |prefix: &str| {
+        inner
+            .split(',')
+            .filter_map(|part| part.trim().strip_prefix(prefix))
+            .map(|n| n.trim().parse::<u32>().unwrap_or(0))
+            .next()
+    };
+    let ahead = count("ahead");
+    let behind = count("behind");
+    let status = status_from_counts(ahead.is_some(), behind.is_some());
+    (status, ahead.unwrap_or(0), behind.unwrap_or(0))
+}
+
+fn status_from_counts(has_ahead: bool, has_behind: bool) -> UpstreamStatus {
     match (has_ahead, has_behind) {
         (true, true) => UpstreamStatus::MergeNeeded,
         (true, false) => UpstreamStatus::LocalIsAheadOfUpstream,
@@ -0,0 +1,90 @@
+use crate::forge::ForgeKind;
+
+/// Settings for a single repository, from the `branch-assistant.*` section of its git
+/// config:
+///
+/// ```text
+/// [branch-assistant]
+///     skip = true
+///     forge = gitlab
+///     defaultBranch = develop
+///     syncStrategy = merge
+///     protected = release/*
+///     remote = fork
+///     reviewer = alice
+///     label = backend
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepoConfig {
+    /// Leave the repository out of `repos` altogether.
+    pub skip: bool,
+    /// The forge, when it can't be told from the remote URL.
+    pub forge: Option<ForgeKind>,
+    pub default_branch: Option<String>,
+    pub sync_strategy: SyncStrategy,
+    /// Protected branch patterns, in addition to those in the config file.
+    pub protected: Vec<String>,
+    /// Where to push branches git has no push remote for, instead of the configured
+    /// remote.
+    pub remote: Option<String>,
+    /// Requested to review new pull requests, in addition to those in the config file.
+    pub reviewers: Vec<String>,
+    /// Added to new pull requests, in addition to those in the config file.
+    pub labels: Vec<String>,
+}
+
+/// How a branch that has diverged from its upstream is brought up to date.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncStrategy {
+    #[default]
+    Rebase,
+    Merge,
+}
+
+pub const SECTION: &str = "branch-assistant";
+
+impl RepoConfig {
+    /// Parses the output of `git config --get-regexp ^branch-assistant\.`, one
+    /// `key value` pair per line. Git lowercases the keys; unknown keys and values
+    /// are ignored.
+    pub fn parse(output: &str) -> Self {
+        let mut config = Self::default();
+        for line in output.lines() {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let Some(name) = key
+                .strip_prefix(SECTION)
+                .and_then(|rest| rest.strip_prefix('.'))
+            else {
+                continue;
+            };
+            let value = value.trim();
+            match name.to_ascii_lowercase().as_str() {
+                "skip" => config.skip = parse_bool(value).unwrap_or(config.skip),
+                "forge" => config.forge = ForgeKind::parse(value),
+                "defaultbranch" if !value.is_empty() => {
+                    config.default_branch = Some(value.to_string())
+                }
+                "syncstrategy" => match value.to_ascii_lowercase().as_str() {
+                    "rebase" => config.sync_strategy = SyncStrategy::Rebase,
+                    "merge" => config.sync_strategy = SyncStrategy::Merge,
+                    _ => {}
+                },
+                "protected" if !value.is_empty() => config.protected.push(value.to_string()),
+                "remote" if !value.is_empty() => config.remote = Some(value.to_string()),
+                "reviewer" if !value.is_empty() => config.reviewers.push(value.to_string()),
+                "label" if !value.is_empty() => config.labels.push(value.to_string()),
+                _ => {}
+            }
+        }
+        config
+    }
+}
+
+/// Git's spellings of booleans; a key without a value is true.
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "" | "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
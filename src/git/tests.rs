@@ -1,7 +1,10 @@
 #[cfg(test)]
 mod tests {
     use super::super::parse_branches;
-    use crate::git::{Branch, GitRepo, Upstream, UpstreamStatus};
+    use crate::forge::ForgeKind;
+    use crate::git::{
+        Branch, CommitMessage, GitRepo, RepoConfig, SyncStrategy, Upstream, UpstreamStatus,
+    };
     use anyhow::Result;
     use std::path::PathBuf;
     use std::process::Command;
@@ -10,8 +13,14 @@ mod tests {
     fn branch_needs_action_when_no_upstream() {
         let branch = Branch {
             refname: "feature".to_string(),
+            tip: "0000000".into(),
             upstream: None,
             worktree_path: None,
+            merged_into: None,
+            push_remote: None,
+            push_target: None,
+            protected: false,
+            pull_request: None,
         };
         assert!(branch.needs_action());
     }
@@ -20,11 +29,20 @@ mod tests {
     fn branch_needs_action_when_upstream_status_not_identical() {
         let branch = Branch {
             refname: "feature".to_string(),
+            tip: "0000000".into(),
             upstream: Some(Upstream {
                 name: "origin/feature".to_string(),
+                remote: "origin".into(),
                 status: UpstreamStatus::MergeNeeded,
+                ahead: 0,
+                behind: 0,
             }),
             worktree_path: None,
+            merged_into: None,
+            push_remote: None,
+            push_target: None,
+            protected: false,
+            pull_request: None,
         };
         assert!(branch.needs_action());
     }
@@ -33,11 +51,20 @@ mod tests {
     fn branch_no_action_when_identical() {
         let branch = Branch {
             refname: "feature".to_string(),
+            tip: "0000000".into(),
             upstream: Some(Upstream {
                 name: "origin/feature".to_string(),
+                remote: "origin".into(),
                 status: UpstreamStatus::Identical,
+                ahead: 0,
+                behind: 0,
             }),
             worktree_path: None,
+            merged_into: None,
+            push_remote: None,
+            push_target: None,
+            protected: false,
+            pull_request: None,
         };
         assert!(!branch.needs_action());
     }
@@ -77,13 +104,13 @@ mod tests {
     #[test]
     fn parse_branches_handles_all_upstream_states() -> Result<()> {
         let output = "\
-main|origin/main||/repo
-behind|origin/behind|[behind 2]|
-ahead|origin/ahead|[ahead 1]|
-diverged|origin/diverged|[ahead 1, behind 2]|
-gone|origin/gone|[gone]|
-local-only||||
-";
+    main|1111111|origin/main||origin|origin|/repo
+    behind|2222222|origin/behind|[behind 2]|origin|origin|
+    ahead|3333333|origin/ahead|[ahead 1]|origin|origin|
+    diverged|4444444|origin/diverged|[ahead 1, behind 2]|origin|origin|
+    gone|5555555|origin/gone|[gone]|origin|origin|
+    local-only|6666666||||origin|
+    ";
         let branches = parse_branches(output)?;
         assert_eq!(branches.len(), 6);
 
@@ -91,8 +118,12 @@ local-only||||
             branches.iter().map(|b| (b.refname.as_str(), b)).collect();
 
         let main = by_name["main"];
-        assert_eq!(main.upstream.as_ref().map(|u| u.status), Some(UpstreamStatus::Identical));
+        assert_eq!(
+            main.upstream.as_ref().map(|u| u.status),
+            Some(UpstreamStatus::Identical)
+        );
         assert_eq!(main.worktree_path, Some(PathBuf::from("/repo")));
+        assert_eq!(main.tip, "1111111");
 
         assert_eq!(
             by_name["behind"].upstream.as_ref().map(|u| u.status),
@@ -106,6 +137,20 @@ local-only||||
             by_name["diverged"].upstream.as_ref().map(|u| u.status),
             Some(UpstreamStatus::MergeNeeded)
         );
+        assert_eq!(
+            by_name["diverged"]
+                .upstream
+                .as_ref()
+                .map(|u| (u.ahead, u.behind)),
+            Some((1, 2))
+        );
+        assert_eq!(
+            by_name["behind"]
+                .upstream
+                .as_ref()
+                .map(|u| (u.ahead, u.behind)),
+            Some((0, 2))
+        );
         assert_eq!(
             by_name["gone"].upstream.as_ref().map(|u| u.status),
             Some(UpstreamStatus::UpstreamIsGone)
@@ -124,4 +169,435 @@ local-only||||
         assert_eq!(refnames, vec!["existing", "master"]);
         Ok(())
     }
+
+    fn git(dir: &std::path::Path, args: &[&str]) -> Result<()> {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .env("GIT_AUTHOR_NAME", "test")
+            .env("GIT_AUTHOR_EMAIL", "test@example.com")
+            .env("GIT_COMMITTER_NAME", "test")
+            .env("GIT_COMMITTER_EMAIL", "test@example.com")
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()?;
+        if !status.success() {
+            return Err(anyhow::anyhow!("git {} failed", args.join(" ")));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn fetch_and_prune_marks_deleted_upstream_as_gone() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let origin = temp.path().join("origin");
+        let clone = temp.path().join("clone");
+        std::fs::create_dir(&origin)?;
+        git(&origin, &["init", "-q", "-b", "main"])?;
+        git(
+            &origin,
+            &["commit", "-q", "--allow-empty", "-m", "Initial commit"],
+        )?;
+        git(&origin, &["branch", "feature"])?;
+        git(temp.path(), &["clone", "-q", "origin", "clone"])?;
+        git(&clone, &["branch", "--track", "feature", "origin/feature"])?;
+        git(&origin, &["branch", "-D", "feature"])?;
+
+        let repo = GitRepo::new(clone);
+        let status_of_feature = |repo: &GitRepo| -> Result<Option<UpstreamStatus>> {
+            Ok(repo
+                .get_branches()?
+                .into_iter()
+                .find(|b| b.refname == "feature")
+                .and_then(|b| b.upstream.map(|u| u.status)))
+        };
+        assert_eq!(status_of_feature(&repo)?, Some(UpstreamStatus::Identical));
+
+        repo.fetch_and_prune()?;
+        assert_eq!(
+            status_of_feature(&repo)?,
+            Some(UpstreamStatus::UpstreamIsGone)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fetch_and_prune_fails_outside_repository() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let repo = GitRepo::new(temp.path().to_path_buf());
+        assert!(repo.fetch_and_prune().is_err());
+        Ok(())
+    }
+
+    fn repo_with_main(temp: &std::path::Path) -> Result<GitRepo> {
+        git(temp, &["init", "-q", "-b", "main"])?;
+        std::fs::write(temp.join("file.txt"), "base\n")?;
+        git(temp, &["add", "file.txt"])?;
+        git(temp, &["commit", "-q", "-m", "Initial commit"])?;
+        Ok(GitRepo::new(temp.to_path_buf()))
+    }
+
+    fn commit_file(dir: &std::path::Path, name: &str, content: &str) -> Result<()> {
+        std::fs::write(dir.join(name), content)?;
+        git(dir, &["add", name])?;
+        git(dir, &["commit", "-q", "-m", &format!("Change {name}")])
+    }
+
+    #[test]
+    fn commit_infos_tell_author_from_committer() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let repo = repo_with_main(temp.path())?;
+        let status = Command::new("git")
+            .args([
+                "commit",
+                "-q",
+                "--allow-empty",
+                "-m",
+                "Fix a | b\n\nDetails",
+            ])
+            .current_dir(temp.path())
+            .env("GIT_AUTHOR_NAME", "Alice")
+            .env("GIT_AUTHOR_EMAIL", "alice@example.com")
+            .env("GIT_AUTHOR_DATE", "2024-03-01T12:00:00Z")
+            .env("GIT_COMMITTER_NAME", "Bob")
+            .env("GIT_COMMITTER_EMAIL", "bob@example.com")
+            .status()?;
+        assert!(status.success());
+
+        let info = &repo.branch_commit_infos()?["main"];
+        assert_eq!(info.author, "Alice");
+        assert_eq!(info.author_email, "alice@example.com");
+        assert_eq!(info.author_date, "2024-03-01");
+        assert_eq!(info.author_timestamp, 1709294400);
+        assert_eq!(info.committer, "Bob");
+        assert_eq!(info.committer_email, "bob@example.com");
+        assert_eq!(info.subject, "Fix a | b");
+        Ok(())
+    }
+
+    #[test]
+    fn squash_merged_branch_is_detected() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let repo = repo_with_main(temp.path())?;
+        git(temp.path(), &["checkout", "-q", "-b", "feature"])?;
+        commit_file(temp.path(), "a.txt", "a\n")?;
+        commit_file(temp.path(), "b.txt", "b\n")?;
+        git(temp.path(), &["checkout", "-q", "main"])?;
+        git(temp.path(), &["merge", "-q", "--squash", "feature"])?;
+        git(temp.path(), &["commit", "-q", "-m", "Squashed feature"])?;
+
+        assert!(repo.is_merged_into("feature", "main", &mut None)?);
+
+        let mut branches = repo.get_branches()?;
+        repo.detect_merged_branches(&mut branches);
+        let feature = branches.iter().find(|b| b.refname == "feature").unwrap();
+        assert_eq!(feature.merged_into.as_deref(), Some("main"));
+        let main = branches.iter().find(|b| b.refname == "main").unwrap();
+        assert!(main.merged_into.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn squash_detection_writes_no_objects() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let repo = repo_with_main(temp.path())?;
+        git(temp.path(), &["checkout", "-q", "-b", "feature"])?;
+        commit_file(temp.path(), "a.txt", "a\n")?;
+        git(temp.path(), &["checkout", "-q", "main"])?;
+        commit_file(temp.path(), "other.txt", "other\n")?;
+        let objects = || -> Result<Vec<u8>> {
+            let output = Command::new("git")
+                .args(["count-objects"])
+                .current_dir(temp.path())
+                .output()?;
+            Ok(output.stdout)
+        };
+        let before = objects()?;
+
+        assert!(!repo.is_merged_into("feature", "main", &mut None)?);
+        assert_eq!(objects()?, before);
+        Ok(())
+    }
+
+    #[test]
+    fn branch_without_commits_of_its_own_is_not_merged() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let repo = repo_with_main(temp.path())?;
+        git(temp.path(), &["branch", "fresh"])?;
+        git(temp.path(), &["checkout", "-q", "-b", "feature"])?;
+        commit_file(temp.path(), "a.txt", "a\n")?;
+        git(temp.path(), &["checkout", "-q", "main"])?;
+        git(
+            temp.path(),
+            &["merge", "-q", "--no-ff", "-m", "Merge feature", "feature"],
+        )?;
+
+        assert!(!repo.is_merged_into("fresh", "main", &mut None)?);
+        assert!(repo.is_merged_into("feature", "main", &mut None)?);
+        Ok(())
+    }
+
+    #[test]
+    fn fast_forward_merged_branch_is_detected() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let repo = repo_with_main(temp.path())?;
+        git(temp.path(), &["checkout", "-q", "-b", "feature"])?;
+        commit_file(temp.path(), "a.txt", "a\n")?;
+        git(temp.path(), &["checkout", "-q", "main"])?;
+        git(temp.path(), &["merge", "-q", "--ff-only", "feature"])?;
+
+        assert!(repo.is_merged_into("feature", "main", &mut None)?);
+        Ok(())
+    }
+
+    #[test]
+    fn stacked_branch_finds_its_parent() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let repo = repo_with_main(temp.path())?;
+        git(temp.path(), &["checkout", "-q", "-b", "feature"])?;
+        commit_file(temp.path(), "a.txt", "a\n")?;
+        git(temp.path(), &["checkout", "-q", "-b", "stacked"])?;
+        commit_file(temp.path(), "b.txt", "b\n")?;
+
+        assert_eq!(repo.stack_parent("stacked").as_deref(), Some("feature"));
+        assert_eq!(repo.stack_parent("feature"), None);
+        assert_eq!(
+            repo.commit_messages("feature", "stacked")?,
+            vec![CommitMessage {
+                subject: "Change b.txt".into(),
+                body: String::new(),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rebase_merged_branch_is_detected() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let repo = repo_with_main(temp.path())?;
+        git(temp.path(), &["checkout", "-q", "-b", "feature"])?;
+        commit_file(temp.path(), "a.txt", "a\n")?;
+        git(temp.path(), &["checkout", "-q", "main"])?;
+        commit_file(temp.path(), "other.txt", "other\n")?;
+        git(temp.path(), &["cherry-pick", "feature"])?;
+
+        assert!(repo.is_merged_into("feature", "main", &mut None)?);
+        Ok(())
+    }
+
+    #[test]
+    fn unmerged_branch_is_not_detected() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let repo = repo_with_main(temp.path())?;
+        git(temp.path(), &["checkout", "-q", "-b", "feature"])?;
+        commit_file(temp.path(), "a.txt", "a\n")?;
+        git(temp.path(), &["checkout", "-q", "main"])?;
+        commit_file(temp.path(), "other.txt", "other\n")?;
+
+        assert!(!repo.is_merged_into("feature", "main", &mut None)?);
+        Ok(())
+    }
+
+    #[test]
+    fn triangular_branch_is_compared_against_push_remote() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let upstream = temp.path().join("upstream");
+        std::fs::create_dir(&upstream)?;
+        git(&upstream, &["init", "-q", "-b", "main"])?;
+        git(
+            &upstream,
+            &["commit", "-q", "--allow-empty", "-m", "Initial commit"],
+        )?;
+        git(
+            temp.path(),
+            &["clone", "-q", "--bare", "upstream", "fork.git"],
+        )?;
+        git(
+            temp.path(),
+            &["clone", "-q", "-o", "upstream", "upstream", "work"],
+        )?;
+        let work = temp.path().join("work");
+        git(&work, &["remote", "add", "fork", "../fork.git"])?;
+        git(&work, &["config", "remote.pushDefault", "fork"])?;
+        git(
+            &work,
+            &[
+                "checkout",
+                "-q",
+                "-b",
+                "feature",
+                "--track",
+                "upstream/main",
+            ],
+        )?;
+        git(&work, &["commit", "-q", "--allow-empty", "-m", "Feature"])?;
+
+        let repo = GitRepo::new(work.clone());
+        let feature = |repo: &GitRepo| -> Result<Branch> {
+            Ok(repo
+                .get_branches()?
+                .into_iter()
+                .find(|b| b.refname == "feature")
+                .unwrap())
+        };
+
+        let branch = feature(&repo)?;
+        assert_eq!(branch.push_remote(), "fork");
+        assert_eq!(
+            branch.push_target.as_ref().map(|p| p.status),
+            Some(UpstreamStatus::UpstreamIsGone)
+        );
+        assert!(branch.needs_action());
+
+        repo.push("feature", branch.push_remote())?;
+        let branch = feature(&repo)?;
+        let push_target = branch.push_target.as_ref().unwrap();
+        assert_eq!(push_target.name, "fork/feature");
+        assert_eq!(push_target.status, UpstreamStatus::Identical);
+        assert_eq!(
+            branch.upstream.as_ref().map(|u| u.status),
+            Some(UpstreamStatus::LocalIsAheadOfUpstream)
+        );
+        assert!(!branch.needs_action());
+
+        git(&work, &["commit", "-q", "--allow-empty", "-m", "More"])?;
+        let branch = feature(&repo)?;
+        let push_target = branch.push_target.as_ref().unwrap();
+        assert_eq!(push_target.status, UpstreamStatus::LocalIsAheadOfUpstream);
+        assert_eq!(push_target.ahead, 1);
+
+        let main = repo
+            .get_branches()?
+            .into_iter()
+            .find(|b| b.refname == "main")
+            .unwrap();
+        assert!(main.push_target.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn default_branch_comes_from_remote_head() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let origin = temp.path().join("origin");
+        std::fs::create_dir(&origin)?;
+        git(&origin, &["init", "-q", "-b", "trunk"])?;
+        git(
+            &origin,
+            &["commit", "-q", "--allow-empty", "-m", "Initial commit"],
+        )?;
+        git(&origin, &["branch", "main"])?;
+        git(temp.path(), &["clone", "-q", "origin", "clone"])?;
+
+        let repo = GitRepo::new(temp.path().join("clone"));
+        assert_eq!(repo.default_branch()?, "trunk");
+        Ok(())
+    }
+
+    #[test]
+    fn default_branch_uses_configured_override() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let repo = repo_with_main(temp.path())?;
+        git(temp.path(), &["branch", "develop"])?;
+        git(
+            temp.path(),
+            &["config", "branch-assistant.defaultBranch", "develop"],
+        )?;
+        assert_eq!(repo.default_branch()?, "develop");
+        Ok(())
+    }
+
+    #[test]
+    fn default_branch_falls_back_to_well_known_names() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        git(temp.path(), &["init", "-q", "-b", "master"])?;
+        git(
+            temp.path(),
+            &["commit", "-q", "--allow-empty", "-m", "Initial commit"],
+        )?;
+        let repo = GitRepo::new(temp.path().to_path_buf());
+        assert_eq!(repo.default_branch()?, "master");
+        Ok(())
+    }
+
+    #[test]
+    fn deleted_branch_is_restored_from_backup_ref() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let repo = repo_with_main(temp.path())?;
+        git(temp.path(), &["checkout", "-q", "-b", "feature/x"])?;
+        commit_file(temp.path(), "a.txt", "a\n")?;
+        git(temp.path(), &["checkout", "-q", "main"])?;
+        let tip = repo.branch_tip("feature/x")?.unwrap();
+
+        let refname = repo.create_backup_ref("feature/x", &tip, 1_700_000_000)?;
+        repo.delete_branch_forcefully("feature/x")?;
+        assert_eq!(repo.branch_tip("feature/x")?, None);
+
+        let backups = repo.backups()?;
+        assert_eq!(
+            backups,
+            vec![super::super::Backup {
+                refname,
+                branch: "feature/x".into(),
+                tip: tip.clone(),
+                timestamp: 1_700_000_000,
+            }]
+        );
+
+        repo.restore_branch("feature/x", &backups[0].tip)?;
+        assert_eq!(repo.branch_tip("feature/x")?, Some(tip));
+        Ok(())
+    }
+
+    #[test]
+    fn repo_config_parses_branch_assistant_section() {
+        let config = RepoConfig::parse(
+            "branch-assistant.skip\n\
+             branch-assistant.forge GitLab\n\
+             branch-assistant.defaultbranch develop\n\
+             branch-assistant.syncstrategy Merge\n\
+             branch-assistant.protected release/*\n\
+             branch-assistant.protected prod\n\
+             branch-assistant.remote fork\n\
+             branch-assistant.reviewer alice\n\
+             branch-assistant.label backend\n\
+             branch-assistant.unknown whatever\n",
+        );
+        assert_eq!(
+            config,
+            RepoConfig {
+                skip: true,
+                forge: Some(ForgeKind::GitLab),
+                default_branch: Some("develop".into()),
+                sync_strategy: SyncStrategy::Merge,
+                protected: vec!["release/*".into(), "prod".into()],
+                remote: Some("fork".into()),
+                reviewers: vec!["alice".into()],
+                labels: vec!["backend".into()],
+            }
+        );
+        assert_eq!(RepoConfig::parse(""), RepoConfig::default());
+    }
+
+    #[test]
+    fn repo_config_is_read_from_git_config() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let dir = temp.path();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .args(args)
+                .current_dir(dir)
+                .status()
+                .expect("failed to run git");
+            assert!(status.success(), "git {args:?} failed");
+        };
+        git(&["init", "-q", "-b", "main"]);
+        git(&["config", "branch-assistant.skip", "false"]);
+        git(&["config", "branch-assistant.defaultBranch", "develop"]);
+
+        let repo = GitRepo::new(dir.to_path_buf());
+        assert!(!repo.config().skip);
+        assert_eq!(repo.config().default_branch.as_deref(), Some("develop"));
+        assert_eq!(repo.default_branch()?, "develop");
+        Ok(())
+    }
 }
@@ -0,0 +1,313 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::cache::now_unix;
+use crate::git::GitRepo;
+
+/// A mutating action the tool performed on a branch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub timestamp: i64,
+    pub repo: PathBuf,
+    pub branch: String,
+    /// What was done, as described in the menu it was chosen from.
+    pub action: String,
+    /// Where the branch pointed before, if it existed.
+    pub old_tip: Option<String>,
+    /// Where the branch pointed after, or `None` if it no longer exists.
+    pub new_tip: Option<String>,
+    /// The commands that were run, in order.
+    pub commands: Vec<String>,
+    pub outcome: JournalOutcome,
+    /// Where the old tip was backed up, for destructive actions.
+    pub backup_ref: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JournalOutcome {
+    Succeeded,
+    Failed(String),
+}
+
+impl<T> From<&Result<T>> for JournalOutcome {
+    fn from(result: &Result<T>) -> Self {
+        match result {
+            Ok(_) => Self::Succeeded,
+            Err(error) => Self::Failed(format!("{error:#}")),
+        }
+    }
+}
+
+/// An append-only log of the actions performed, one JSON object per line, kept under
+/// `$XDG_STATE_HOME/git-branch-assistant/` (or `~/.local/state/...`).
+#[derive(Debug, Clone)]
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn open_default() -> Option<Self> {
+        default_state_root().map(|root| Self::new(root.join("journal.jsonl")))
+    }
+
+    pub fn append(&self, entry: &JournalEntry) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create state dir at {}", parent.display()))?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("failed to open journal {}", self.path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(entry)?)
+            .with_context(|| format!("failed to write journal {}", self.path.display()))
+    }
+
+    /// All entries, oldest first. Lines that can't be parsed are skipped.
+    pub fn read(&self) -> Result<Vec<JournalEntry>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(&self.path)
+            .with_context(|| format!("failed to read journal {}", self.path.display()))?;
+        Ok(content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+}
+
+/// Runs `f` against `repo` and, if it ran any mutating commands, records them in
+/// `journal` along with how `branch` moved and any backup ref `f` noted down.
+/// Failing to write the journal is reported but doesn't fail the action, which
+/// has already happened by then.
+pub fn record<T>(
+    journal: Option<&Journal>,
+    repo: &GitRepo,
+    branch: &str,
+    action: String,
+    old_tip: Option<String>,
+    f: impl FnOnce(&GitRepo, &mut Option<String>) -> Result<T>,
+) -> Result<T> {
+    let mut backup_ref = None;
+    let Some(journal) = journal else {
+        return f(repo, &mut backup_ref);
+    };
+    let (result, commands) = repo.logging_writes(|repo| f(repo, &mut backup_ref));
+    if !commands.is_empty() {
+        let entry = JournalEntry {
+            timestamp: now_unix(),
+            repo: repo.dir().to_path_buf(),
+            branch: branch.to_string(),
+            action,
+            old_tip,
+            new_tip: repo.branch_tip(branch).ok().flatten(),
+            commands,
+            outcome: JournalOutcome::from(&result),
+            backup_ref,
+        };
+        if let Err(error) = journal.append(&entry) {
+            eprintln!("Failed to write to the journal: {error:#}");
+        }
+    }
+    result
+}
+
+/// Formats a unix timestamp as `YYYY-MM-DD HH:MM` in UTC.
+pub fn format_timestamp(timestamp: i64) -> String {
+    let days = timestamp.div_euclid(86_400);
+    let seconds = timestamp.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}",
+        seconds / 3600,
+        seconds % 3600 / 60
+    )
+}
+
+/// Converts days since 1970-01-01 to a proleptic Gregorian date, using Howard
+/// Hinnant's `civil_from_days` algorithm.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn default_state_root() -> Option<PathBuf> {
+    if let Ok(xdg) = std::env::var("XDG_STATE_HOME")
+        && !xdg.is_empty()
+    {
+        return Some(PathBuf::from(xdg).join("git-branch-assistant"));
+    }
+    let home = std::env::var("HOME").ok()?;
+    Some(
+        PathBuf::from(home)
+            .join(".local")
+            .join("state")
+            .join("git-branch-assistant"),
+    )
+}
+
+/// Which journal entries `history` shows.
+#[derive(Debug, Default)]
+pub struct HistoryFilter {
+    /// Only entries for repositories at or below this path.
+    pub repo: Option<PathBuf>,
+    pub branch: Option<String>,
+    pub failed_only: bool,
+}
+
+impl HistoryFilter {
+    pub fn matches(&self, entry: &JournalEntry) -> bool {
+        if let Some(repo) = &self.repo
+            && !entry.repo.starts_with(repo)
+        {
+            return false;
+        }
+        if let Some(branch) = &self.branch
+            && entry.branch != *branch
+        {
+            return false;
+        }
+        !self.failed_only || matches!(entry.outcome, JournalOutcome::Failed(_))
+    }
+}
+
+/// One line describing the entry, followed by its commands when `verbose`.
+pub fn format_entry(entry: &JournalEntry, verbose: bool) -> Vec<String> {
+    let tip = |tip: &Option<String>| match tip {
+        Some(tip) => tip[..tip.len().min(7)].to_string(),
+        None => "(none)".to_string(),
+    };
+    let outcome = match &entry.outcome {
+        JournalOutcome::Succeeded => "ok".to_string(),
+        JournalOutcome::Failed(error) => format!("failed: {error}"),
+    };
+    let mut lines = vec![format!(
+        "{}  {}:{}  {}  {} -> {}  {}",
+        format_timestamp(entry.timestamp),
+        entry.repo.display(),
+        entry.branch,
+        entry.action,
+        tip(&entry.old_tip),
+        tip(&entry.new_tip),
+        outcome
+    )];
+    if verbose {
+        lines.extend(
+            entry
+                .commands
+                .iter()
+                .map(|command| format!("    {command}")),
+        );
+        if let Some(backup_ref) = &entry.backup_ref {
+            lines.push(format!("    backup: {backup_ref}"));
+        }
+    }
+    lines
+}
+
+/// Looks up the journal entry for a backup ref, if the journal has one.
+pub fn entry_for_backup<'a>(
+    entries: &'a [JournalEntry],
+    backup_ref: &str,
+) -> Option<&'a JournalEntry> {
+    entries
+        .iter()
+        .find(|entry| entry.backup_ref.as_deref() == Some(backup_ref))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn deleted_feature() -> JournalEntry {
+        JournalEntry {
+            timestamp: 1_700_000_000,
+            repo: PathBuf::from("/tmp/repo"),
+            branch: "feature".into(),
+            action: "Delete it".into(),
+            old_tip: Some("abc1234def".into()),
+            new_tip: None,
+            commands: vec!["git checkout main".into(), "git branch -D feature".into()],
+            outcome: JournalOutcome::Succeeded,
+            backup_ref: Some("refs/branch-assistant/backup/feature/1700000000-abc1234".into()),
+        }
+    }
+
+    #[test]
+    fn journal_round_trips_entries() -> Result<()> {
+        let temp = tempdir()?;
+        let journal = Journal::new(temp.path().join("state").join("journal.jsonl"));
+        assert!(journal.read()?.is_empty());
+
+        let entry = deleted_feature();
+        journal.append(&entry)?;
+        journal.append(&entry)?;
+        assert_eq!(journal.read()?, vec![entry.clone(), entry]);
+        Ok(())
+    }
+
+    #[test]
+    fn format_timestamp_uses_utc_dates() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14 22:13");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00");
+    }
+
+    #[test]
+    fn history_filter_matches_repo_branch_and_outcome() {
+        let entry = deleted_feature();
+        assert!(HistoryFilter::default().matches(&entry));
+        let in_tmp = HistoryFilter {
+            repo: Some(PathBuf::from("/tmp")),
+            ..HistoryFilter::default()
+        };
+        assert!(in_tmp.matches(&entry));
+        let other_repo = HistoryFilter {
+            repo: Some(PathBuf::from("/tmp/rep")),
+            ..HistoryFilter::default()
+        };
+        assert!(!other_repo.matches(&entry));
+        let other_branch = HistoryFilter {
+            branch: Some("main".into()),
+            ..HistoryFilter::default()
+        };
+        assert!(!other_branch.matches(&entry));
+        let failed_only = HistoryFilter {
+            failed_only: true,
+            ..HistoryFilter::default()
+        };
+        assert!(!failed_only.matches(&entry));
+    }
+
+    #[test]
+    fn format_entry_shows_tips_and_commands() {
+        assert_eq!(
+            format_entry(&deleted_feature(), true),
+            vec![
+                "2023-11-14 22:13  /tmp/repo:feature  Delete it  abc1234 -> (none)  ok",
+                "    git checkout main",
+                "    git branch -D feature",
+                "    backup: refs/branch-assistant/backup/feature/1700000000-abc1234",
+            ]
+        );
+    }
+}
@@ -1,17 +1,38 @@
 use std::path::PathBuf;
 
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 
+use crate::branch_filter::{BranchFilter, parse_age};
+use crate::config::Config;
+use crate::journal::HistoryFilter;
+use crate::output::{OutputFormat, OutputWriter};
+use crate::services::git_repos_list_service::{
+    BranchStatus, Column, GroupBy, ListOrder, ListView, SortKey,
+};
+use crate::settings::Settings;
+use crate::ui::AssumedChoice;
+
+mod auto;
+mod branch_filter;
 mod cache;
 mod cleaner;
 mod commands;
+mod config;
+mod discovery;
 mod env;
+mod forge;
 mod fs_utils;
 mod git;
+mod journal;
+mod manifest;
+mod output;
 mod picker;
+mod plan;
+mod protection;
 mod repository;
 mod services;
+mod settings;
 mod task_result;
 mod ui;
 
@@ -30,44 +51,367 @@ enum Command {
         /// Path to the git repository (defaults to current directory)
         #[arg(short, long)]
         path: Option<PathBuf>,
-        /// Dry run mode - analyze without performing actions or prompting
+        /// Dry run mode - print the commands that would run instead of running them
         #[arg(long)]
         dry: bool,
+        /// In dry run mode, which option to assume at each prompt
+        #[arg(long, value_enum, default_value_t, requires = "dry")]
+        assume: AssumedChoice,
+        #[command(flatten)]
+        fetch: FetchArgs,
+        /// Write the proposed actions to a plan file instead of acting on them
+        #[arg(long, value_name = "FILE", conflicts_with = "dry")]
+        plan: Option<PathBuf>,
+        /// Act by the rules in the config file instead of prompting, then report
+        #[arg(long, conflicts_with = "plan")]
+        auto: bool,
+        /// In dry run mode, how to print what would be done
+        #[arg(
+            long,
+            value_enum,
+            default_value_t,
+            requires = "dry",
+            conflicts_with = "auto"
+        )]
+        output: OutputFormat,
     },
     /// Inspect child directories and highlight git repositories needing attention.
     Repos {
-        /// Path to the directory to search (defaults to current directory)
+        /// Path to the directory to search (defaults to the configured workspace roots,
+        /// or the current directory)
         #[arg(short, long)]
         path: Option<PathBuf>,
-        /// Dry run mode - analyze without performing actions or prompting
-        #[arg(long)]
+        /// Dry run mode - print the commands that would run instead of running them
+        #[arg(long, overrides_with = "no_dry")]
         dry: bool,
-        /// Skip repositories with uncommitted changes
+        /// Run for real, even if dry runs are enabled in the config file
         #[arg(long)]
+        no_dry: bool,
+        /// In dry run mode, which option to assume at each prompt
+        #[arg(long, value_enum, default_value_t)]
+        assume: AssumedChoice,
+        /// Skip repositories with uncommitted changes
+        #[arg(long, overrides_with = "no_skip_dirty_repos")]
         skip_dirty_repos: bool,
+        /// Include repositories with uncommitted changes, even if the config file skips them
+        #[arg(long)]
+        no_skip_dirty_repos: bool,
         /// List every branch across all repos sorted by oldest commit first
         #[arg(long)]
         list: bool,
         /// With --list, prompt to select a branch to check out
         #[arg(short, long, requires = "list")]
         interactive: bool,
+        #[command(flatten)]
+        filter: ListFilterArgs,
+        #[command(flatten)]
+        view: ListViewArgs,
+        #[command(flatten)]
+        fetch: FetchArgs,
+        /// Write the proposed actions to a plan file instead of acting on them
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["dry", "list"])]
+        plan: Option<PathBuf>,
+        /// Act by the rules in the config file instead of prompting, then report
+        #[arg(long, conflicts_with_all = ["plan", "list"])]
+        auto: bool,
+        /// How to print the branch list, or what was found in each repository instead
+        /// of acting on it
+        #[arg(long, value_enum, default_value_t, conflicts_with_all = ["plan", "auto", "interactive"])]
+        output: OutputFormat,
+    },
+    /// Carry out the actions in a plan file, skipping branches that have changed since.
+    Apply {
+        /// The plan file written by `clean --plan` or `repos --plan`
+        plan: PathBuf,
+        /// Dry run mode - print the commands that would run instead of running them
+        #[arg(long)]
+        dry: bool,
+    },
+    /// Show the actions performed on branches, newest first.
+    History {
+        /// Only show actions in repositories at or below this path
+        #[arg(long)]
+        repo: Option<PathBuf>,
+        /// Only show actions on this branch
+        #[arg(long)]
+        branch: Option<String>,
+        /// Only show actions that failed
+        #[arg(long)]
+        failed: bool,
+        /// Show at most this many actions
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+        /// Also show the commands that were run
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Restore a branch from the backup taken before it was deleted or rebased.
+    #[command(alias = "undo")]
+    Restore {
+        /// Path to the git repository (defaults to current directory)
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// Branch to restore to its latest backup; prompts for a backup if omitted
+        branch: Option<String>,
+        /// List the backups, newest first, instead of restoring one
+        #[arg(long, conflicts_with = "branch")]
+        list: bool,
+    },
+    /// Clone the repositories in the workspace manifest that are missing, and report
+    /// repositories that aren't in it.
+    Sync {
+        /// Workspace root with a workspace.toml (defaults to the configured workspace
+        /// roots, or the current directory)
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// Dry run mode - print the commands that would run instead of running them
+        #[arg(long)]
+        dry: bool,
+    },
+    /// Show the forge a repository is hosted on and the pull request for a branch.
+    Forge {
+        /// Path to the git repository (defaults to current directory)
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// Branch to look up the pull request for (defaults to the checked out one)
+        branch: Option<String>,
+    },
+    /// Work with workspace manifests.
+    Manifest {
+        #[command(subcommand)]
+        command: ManifestCommand,
+    },
+    /// Inspect the configuration.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
     },
 }
 
+#[derive(Subcommand)]
+enum ManifestCommand {
+    /// Print a manifest of the repositories found in a workspace root.
+    Export {
+        /// Workspace root to scan (defaults to the configured workspace root, or the
+        /// current directory)
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// Write the manifest to this file instead of printing it
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the configuration in effect, including defaults.
+    Show,
+}
+
+/// Picks between a flag, its `--no-` counterpart and the config file default.
+fn resolve_flag(flag: bool, no_flag: bool, default: bool) -> bool {
+    if no_flag { false } else { flag || default }
+}
+
+#[derive(Args)]
+struct FetchArgs {
+    /// Run `git fetch --prune` in each repository before analysing branches
+    #[arg(long, overrides_with = "no_fetch")]
+    fetch: bool,
+    /// Don't fetch, even if enabled in the config file
+    #[arg(long)]
+    no_fetch: bool,
+}
+
+impl FetchArgs {
+    fn settings(&self, config: &Config) -> Settings {
+        Settings::from_config(config, self.resolve(config))
+    }
+
+    fn resolve(&self, config: &Config) -> bool {
+        resolve_flag(self.fetch, self.no_fetch, config.fetch)
+    }
+}
+
+/// Which branches `repos --list` shows.
+#[derive(Args)]
+struct ListFilterArgs {
+    /// With --list, only branches with one of these statuses, such as `behind,gone`
+    #[arg(long, value_delimiter = ',', requires = "list")]
+    status: Vec<BranchStatus>,
+    /// With --list, only repositories whose name matches this glob pattern
+    #[arg(long, value_name = "GLOB", requires = "list")]
+    repo: Option<String>,
+    /// With --list, only branches whose name matches this glob pattern
+    #[arg(long, value_name = "GLOB", requires = "list")]
+    branch: Option<String>,
+    /// With --list, only branches last committed to by someone with this in their
+    /// name, or by you with `me`
+    #[arg(long, value_name = "NAME", requires = "list")]
+    committer: Option<String>,
+    /// With --list, only branches whose latest commit is at least this old, such as `90d`
+    #[arg(long, value_name = "AGE", value_parser = parse_age, requires = "list")]
+    older_than: Option<i64>,
+    /// With --list, only branches whose latest commit is newer than this, such as `1w`
+    #[arg(long, value_name = "AGE", value_parser = parse_age, requires = "list")]
+    newer_than: Option<i64>,
+}
+
+impl From<ListFilterArgs> for BranchFilter {
+    fn from(args: ListFilterArgs) -> Self {
+        Self {
+            statuses: args.status,
+            repo: args.repo,
+            branch: args.branch,
+            committer: args.committer,
+            older_than: args.older_than,
+            newer_than: args.newer_than,
+        }
+    }
+}
+
+/// How `repos --list` arranges and shows branches, over the config file's defaults.
+#[derive(Args)]
+struct ListViewArgs {
+    /// With --list, what to sort branches by
+    #[arg(long, value_enum, requires = "list")]
+    sort: Option<SortKey>,
+    /// With --list, sort in the opposite order
+    #[arg(long, overrides_with = "no_reverse", requires = "list")]
+    reverse: bool,
+    /// Sort in the usual order, even if the config file reverses it
+    #[arg(long)]
+    no_reverse: bool,
+    /// With --list, put branches under a header for each repo or status
+    #[arg(long, value_enum, requires = "list")]
+    group_by: Option<GroupBy>,
+    /// With --list, the columns to show, such as `date,author,subject,branch`
+    #[arg(long, value_enum, value_delimiter = ',', requires = "list")]
+    columns: Vec<Column>,
+}
+
+impl ListViewArgs {
+    fn order(&self, config: &Config) -> ListOrder {
+        ListOrder {
+            sort: self.sort.unwrap_or(config.repos.sort),
+            reverse: resolve_flag(self.reverse, self.no_reverse, config.repos.reverse),
+            group_by: self.group_by.unwrap_or(config.repos.group_by),
+        }
+    }
+
+    fn columns(&self, config: &Config) -> Vec<Column> {
+        [&self.columns, &config.repos.columns]
+            .into_iter()
+            .find(|columns| !columns.is_empty())
+            .cloned()
+            .unwrap_or_else(|| Column::DEFAULT.to_vec())
+    }
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    let config = Config::load()?;
 
     match cli.command {
-        Command::Clean { path, dry } => commands::git_clean::run(path, dry)?,
+        Command::Clean {
+            path,
+            dry,
+            assume,
+            fetch,
+            plan,
+            auto,
+            output,
+        } => match plan {
+            Some(plan) => commands::git_clean::plan(path, &fetch.settings(&config), &plan)?,
+            None if auto => {
+                let exit_code = commands::git_clean::auto(path, dry, &fetch.settings(&config))?;
+                std::process::exit(exit_code);
+            }
+            None => commands::git_clean::run(path, dry, assume, output, &fetch.settings(&config))?,
+        },
         Command::Repos {
             path,
             dry,
+            no_dry,
+            assume,
             skip_dirty_repos,
+            no_skip_dirty_repos,
             list,
             interactive,
+            filter,
+            view,
+            fetch,
+            plan,
+            auto,
+            output,
         } => {
-            let exit_code =
-                commands::git_repos::run(path, dry, skip_dirty_repos, list, interactive)?;
+            let skip_dirty_repos = resolve_flag(
+                skip_dirty_repos,
+                no_skip_dirty_repos,
+                config.repos.skip_dirty_repos,
+            );
+            if let Some(plan) = plan {
+                return commands::git_repos::plan(
+                    path,
+                    skip_dirty_repos,
+                    &fetch.settings(&config),
+                    &plan,
+                );
+            }
+            let dry = resolve_flag(dry, no_dry, config.repos.dry);
+            let settings = fetch.settings(&config);
+            let exit_code = if list {
+                let view = ListView {
+                    filter: filter.into(),
+                    order: view.order(&config),
+                    columns: view.columns(&config),
+                };
+                commands::git_repos::list(path, dry, interactive, output, view, &settings)?
+            } else if auto {
+                commands::git_repos::auto(path, dry, skip_dirty_repos, &settings)?
+            } else if let Some(writer) = OutputWriter::new(output) {
+                commands::git_repos::report(path, skip_dirty_repos, &settings, writer)?
+            } else {
+                commands::git_repos::run(path, dry, assume, skip_dirty_repos, &settings)?
+            };
+            std::process::exit(exit_code);
+        }
+        Command::History {
+            repo,
+            branch,
+            failed,
+            limit,
+            verbose,
+        } => {
+            let filter = HistoryFilter {
+                repo: commands::history::repo_filter(repo)?,
+                branch,
+                failed_only: failed,
+            };
+            commands::history::run(filter, limit, verbose)?
+        }
+        Command::Restore { path, branch, list } => commands::restore::run(path, branch, list)?,
+        Command::Sync { path, dry } => {
+            let settings = Settings::from_config(&config, false);
+            let exit_code = commands::sync::run(path, dry, &settings)?;
+            std::process::exit(exit_code);
+        }
+        Command::Forge { path, branch } => {
+            commands::forge::run(path, branch, &Settings::from_config(&config, false))?
+        }
+        Command::Manifest {
+            command: ManifestCommand::Export { path, output },
+        } => commands::manifest::export(
+            path,
+            output.as_deref(),
+            &Settings::from_config(&config, false),
+        )?,
+        Command::Config {
+            command: ConfigCommand::Show,
+        } => commands::config::show(&config)?,
+        Command::Apply { plan, dry } => {
+            let settings = Settings::from_config(&config, false);
+            let exit_code = commands::apply::run(&plan, dry, &settings)?;
             std::process::exit(exit_code);
         }
     }
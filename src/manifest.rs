@@ -0,0 +1,105 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// The manifest's file name, looked for in each workspace root.
+pub const MANIFEST_FILE: &str = "workspace.toml";
+
+/// The repositories a workspace is expected to contain, so that `repos` can work on
+/// exactly those and `sync` can clone the ones that are missing.
+///
+/// ```toml
+/// [[repo]]
+/// path = "org/service"
+/// url = "git@github.com:org/service.git"
+/// default_branch = "develop"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(rename = "repo", default)]
+    pub repos: Vec<ManifestRepo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestRepo {
+    /// Where the repository lives, relative to the workspace root.
+    pub path: PathBuf,
+    /// The URL to clone it from.
+    pub url: String,
+    /// Written to the clone's `branch-assistant.defaultBranch` git config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_branch: Option<String>,
+}
+
+impl Manifest {
+    /// The manifest in `root`, if there is one.
+    pub fn find(root: &Path) -> Result<Option<Self>> {
+        let path = root.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Self::read(&path).map(Some)
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read manifest {}", path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("failed to parse manifest {}", path.display()))
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    /// Whether `relative`, a path below the workspace root, is one of the repositories.
+    pub fn contains(&self, relative: &Path) -> bool {
+        self.repos.iter().any(|repo| repo.path == relative)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_round_trips_through_toml() -> Result<()> {
+        let manifest = Manifest {
+            repos: vec![
+                ManifestRepo {
+                    path: PathBuf::from("org/service"),
+                    url: "git@example.com:org/service.git".into(),
+                    default_branch: Some("develop".into()),
+                },
+                ManifestRepo {
+                    path: PathBuf::from("tool"),
+                    url: "https://example.com/tool.git".into(),
+                    default_branch: None,
+                },
+            ],
+        };
+        let toml = manifest.to_toml()?;
+        assert!(toml.contains("[[repo]]"));
+        assert!(!toml.contains("default_branch = \"\""));
+        assert_eq!(toml::from_str::<Manifest>(&toml)?, manifest);
+        assert!(manifest.contains(Path::new("org/service")));
+        assert!(!manifest.contains(Path::new("org")));
+        Ok(())
+    }
+
+    #[test]
+    fn missing_manifest_is_none() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        assert_eq!(Manifest::find(temp.path())?, None);
+        fs::write(
+            temp.path().join(MANIFEST_FILE),
+            "[[repo]]\npath = \"a\"\nurl = \"u\"\n",
+        )?;
+        assert_eq!(Manifest::find(temp.path())?.unwrap().repos.len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::cleaner::BranchOutcome;
+use crate::forge::PullRequest;
+use crate::git::Branch;
+use crate::services::git_repos_list_service::{BranchListEntry, BranchStatus, branch_status};
+
+/// The version of the records printed with `--output json` or `--output ndjson`. Fields
+/// may be added without changing it, but it goes up whenever a field is removed,
+/// renamed or changes meaning.
+pub const OUTPUT_VERSION: u32 = 1;
+
+/// How results are printed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned text for reading.
+    #[default]
+    Text,
+    /// A single JSON document with every record, printed at the end.
+    Json,
+    /// One JSON record per line, printed as soon as it is known.
+    Ndjson,
+}
+
+/// One thing a command reports in machine-readable output, tagged with its `type`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Record {
+    /// A branch in the `repos --list` listing.
+    Branch(Box<BranchListEntry>),
+    /// What `repos` found in a repository.
+    Repo(RepoRecord),
+    /// What a dry run would do with a branch.
+    Action(ActionRecord),
+    /// A repository that couldn't be read while listing branches.
+    Problem { path: PathBuf, problem: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RepoRecord {
+    pub path: PathBuf,
+    pub state: RepoState,
+    /// The branches needing action; empty unless `state` is `needs_action`.
+    pub branches: Vec<BranchRecord>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RepoState {
+    Clean,
+    NeedsAction,
+    NotDirectory,
+    NotGitRepository,
+    DubiousOwnership,
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BranchRecord {
+    pub branch: String,
+    pub tip: String,
+    pub status: BranchStatus,
+    pub upstream: Option<String>,
+    pub ahead: u32,
+    pub behind: u32,
+    pub merged_into: Option<String>,
+    pub worktree_path: Option<PathBuf>,
+    pub protected: bool,
+    pub pull_request: Option<PullRequest>,
+}
+
+impl From<&Branch> for BranchRecord {
+    fn from(branch: &Branch) -> Self {
+        Self {
+            branch: branch.refname.clone(),
+            tip: branch.tip.clone(),
+            status: branch_status(branch),
+            upstream: branch
+                .upstream
+                .as_ref()
+                .map(|upstream| upstream.name.clone()),
+            ahead: branch
+                .upstream
+                .as_ref()
+                .map_or(0, |upstream| upstream.ahead),
+            behind: branch
+                .upstream
+                .as_ref()
+                .map_or(0, |upstream| upstream.behind),
+            merged_into: branch.merged_into.clone(),
+            worktree_path: branch.worktree_path.clone(),
+            protected: branch.protected,
+            pull_request: branch.pull_request.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ActionRecord {
+    pub repo: PathBuf,
+    pub branch: String,
+    pub status: BranchStatus,
+    #[serde(flatten)]
+    pub outcome: BranchOutcome,
+    /// The commands that would have run.
+    pub commands: Vec<String>,
+}
+
+/// Prints records as JSON: each one as soon as it is emitted for NDJSON, or all of them
+/// in one document when finished for JSON.
+pub struct OutputWriter {
+    format: OutputFormat,
+    records: Vec<Record>,
+}
+
+impl OutputWriter {
+    /// A writer for `format`, or `None` for text, which every command prints its own way.
+    pub fn new(format: OutputFormat) -> Option<Self> {
+        match format {
+            OutputFormat::Text => None,
+            OutputFormat::Json | OutputFormat::Ndjson => Some(Self {
+                format,
+                records: Vec::new(),
+            }),
+        }
+    }
+
+    pub fn emit(&mut self, record: Record) -> Result<()> {
+        if self.format == OutputFormat::Ndjson {
+            println!("{}", render_line(&record)?);
+        } else {
+            self.records.push(record);
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<()> {
+        if self.format == OutputFormat::Json {
+            println!("{}", render_document(&self.records)?);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    version: u32,
+    #[serde(flatten)]
+    record: &'a Record,
+}
+
+#[derive(Serialize)]
+struct Document<'a> {
+    version: u32,
+    records: &'a [Record],
+}
+
+fn render_line(record: &Record) -> Result<String> {
+    Ok(serde_json::to_string(&Line {
+        version: OUTPUT_VERSION,
+        record,
+    })?)
+}
+
+fn render_document(records: &[Record]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(&Document {
+        version: OUTPUT_VERSION,
+        records,
+    })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    fn action() -> Record {
+        Record::Action(ActionRecord {
+            repo: PathBuf::from("/work/repo"),
+            branch: "feature".into(),
+            status: BranchStatus::LocalAhead,
+            outcome: BranchOutcome::Done("Push to origin".into()),
+            commands: vec!["git push origin feature".into()],
+        })
+    }
+
+    #[test]
+    fn ndjson_lines_carry_the_version_and_type() -> Result<()> {
+        let line: Value = serde_json::from_str(&render_line(&action())?)?;
+        assert_eq!(
+            line,
+            json!({
+                "version": OUTPUT_VERSION,
+                "type": "action",
+                "repo": "/work/repo",
+                "branch": "feature",
+                "status": "LocalAhead",
+                "outcome": "done",
+                "detail": "Push to origin",
+                "commands": ["git push origin feature"],
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn json_document_wraps_the_records() -> Result<()> {
+        let problem = Record::Problem {
+            path: PathBuf::from("/work/broken"),
+            problem: "not a git repository".into(),
+        };
+        let document: Value = serde_json::from_str(&render_document(&[action(), problem])?)?;
+        assert_eq!(document["version"], json!(OUTPUT_VERSION));
+        assert_eq!(document["records"][0]["type"], "action");
+        assert_eq!(
+            document["records"][1],
+            json!({"type": "problem", "path": "/work/broken", "problem": "not a git repository"})
+        );
+        Ok(())
+    }
+}
@@ -6,14 +6,16 @@ use std::time::Duration;
 use anyhow::Result;
 use console::{Key, Term};
 
-use crate::services::git_repos_list_service::{BranchListEntry, format_entry_lines};
+use crate::services::git_repos_list_service::{
+    BranchListEntry, Column, GroupBy, ListRow, format_rows,
+};
 
 pub fn stderr_is_terminal() -> bool {
     Term::stderr().is_term()
 }
 
 pub enum PickerOutcome {
-    Picked(BranchListEntry),
+    Picked(Box<BranchListEntry>),
     Cancelled,
 }
 
@@ -26,6 +28,8 @@ enum Event {
 
 struct State {
     entries: Vec<BranchListEntry>,
+    group_by: GroupBy,
+    columns: Vec<Column>,
     selected: usize,
     refreshing: bool,
     rendered_rows: usize,
@@ -34,6 +38,8 @@ struct State {
 pub fn run(
     initial: Vec<BranchListEntry>,
     refresh: Option<Receiver<Vec<BranchListEntry>>>,
+    group_by: GroupBy,
+    columns: Vec<Column>,
 ) -> Result<PickerOutcome> {
     let mut term = Term::stderr();
     let (event_tx, event_rx) = mpsc::channel::<Event>();
@@ -74,6 +80,8 @@ pub fn run(
 
     let mut state = State {
         entries: initial,
+        group_by,
+        columns,
         selected: 0,
         refreshing,
         rendered_rows: 0,
@@ -90,7 +98,7 @@ pub fn run(
             EventResult::Continue => {}
             EventResult::Pick => {
                 if let Some(entry) = state.entries.get(state.selected).cloned() {
-                    break PickerOutcome::Picked(entry);
+                    break PickerOutcome::Picked(Box::new(entry));
                 }
             }
             EventResult::Cancel => break PickerOutcome::Cancelled,
@@ -105,7 +113,7 @@ pub fn run(
                             return finish(
                                 &mut term,
                                 state.rendered_rows,
-                                PickerOutcome::Picked(entry),
+                                PickerOutcome::Picked(Box::new(entry)),
                             );
                         }
                     }
@@ -202,29 +210,52 @@ fn render(term: &mut Term, state: &mut State) -> Result<()> {
     };
     writeln!(term, "{header}")?;
 
-    let lines = format_entry_lines(&state.entries);
-    let height = term.size().0 as usize;
-    let max_visible = height.saturating_sub(3).max(1);
-    let (top, bottom) = visible_window(state.selected, lines.len(), max_visible);
+    let rows = format_rows(&state.entries, state.group_by, &state.columns);
+    let (height, width) = term.size();
+    // Leaves room for the header above the rows and the selected branch's details below.
+    let max_visible = (height as usize).saturating_sub(4).max(1);
+    let selected_row = rows
+        .iter()
+        .position(|row| matches!(row, ListRow::Entry(index, _) if *index == state.selected))
+        .unwrap_or(0);
+    let (top, bottom) = visible_window(selected_row, rows.len(), max_visible);
 
-    if lines.is_empty() {
+    if rows.is_empty() {
         writeln!(term, "  (no branches)")?;
         state.rendered_rows = 2;
         return Ok(());
     }
 
-    for (idx, line) in lines.iter().enumerate().take(bottom).skip(top) {
-        if idx == state.selected {
-            writeln!(term, "> {line}")?;
-        } else {
-            writeln!(term, "  {line}")?;
+    for row in rows.iter().take(bottom).skip(top) {
+        match row {
+            ListRow::Header(header) => writeln!(term, "{header}")?,
+            ListRow::Entry(index, line) if *index == state.selected => writeln!(term, "> {line}")?,
+            ListRow::Entry(_, line) => writeln!(term, "  {line}")?,
         }
     }
-
-    state.rendered_rows = 1 + (bottom - top);
+    let details = state
+        .entries
+        .get(state.selected)
+        .map(describe)
+        .unwrap_or_default();
+    writeln!(
+        term,
+        "{}",
+        console::truncate_str(&details, width as usize, "\u{2026}")
+    )?;
+
+    state.rendered_rows = 2 + (bottom - top);
     Ok(())
 }
 
+/// Who wrote the branch's latest commit, when, and what it is about.
+fn describe(entry: &BranchListEntry) -> String {
+    format!(
+        "{} <{}>, {}: {}",
+        entry.author, entry.author_email, entry.author_date, entry.subject
+    )
+}
+
 fn visible_window(selected: usize, total: usize, max_visible: usize) -> (usize, usize) {
     if total == 0 {
         return (0, 0);
@@ -259,16 +290,28 @@ mod tests {
             repo_path: PathBuf::from("/tmp").join(repo),
             refname: refname.to_string(),
             status: BranchStatus::Identical,
+            ahead: 0,
+            behind: 0,
             commit_timestamp: 1000,
             commit_date: "2024-01-01".to_string(),
             committer: "alice".to_string(),
+            committer_email: "alice@example.com".to_string(),
+            author: "alice".to_string(),
+            author_email: "alice@example.com".to_string(),
+            author_timestamp: 1000,
+            author_date: "2024-01-01".to_string(),
+            subject: "Add feature".to_string(),
             worktree_path: None,
+            protected: false,
+            pull_request: None,
         }
     }
 
     fn state(entries: Vec<BranchListEntry>, selected: usize) -> State {
         State {
             entries,
+            group_by: GroupBy::None,
+            columns: Column::DEFAULT.to_vec(),
             selected,
             refreshing: true,
             rendered_rows: 0,
@@ -314,6 +357,14 @@ mod tests {
         assert_eq!(s.selected, 0);
     }
 
+    #[test]
+    fn details_show_author_and_subject() {
+        assert_eq!(
+            describe(&make_entry("a", "main")),
+            "alice <alice@example.com>, 2024-01-01: Add feature"
+        );
+    }
+
     #[test]
     fn visible_window_centers_on_selection() {
         assert_eq!(visible_window(0, 10, 5), (0, 5));
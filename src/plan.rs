@@ -0,0 +1,291 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::cleaner::{ActionResult, BranchAction, GitCleaner, propose};
+use crate::config::DefaultActions;
+use crate::git::backend::SharedBackend;
+use crate::git::{Branch, GitRepo};
+use crate::services::git_repos_list_service::{BranchStatus, branch_status};
+use crate::settings::Settings;
+use crate::ui::NoPrompt;
+
+const PLAN_VERSION: u32 = 1;
+
+/// The actions `clean --plan` and `repos --plan` propose, written to a file so that
+/// they can be reviewed, edited and then carried out by `apply`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Plan {
+    pub version: u32,
+    pub entries: Vec<PlanEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanEntry {
+    pub repo: PathBuf,
+    pub branch: String,
+    /// The commit the branch pointed to when the plan was made.
+    pub tip: String,
+    pub status: BranchStatus,
+    pub message: String,
+    pub action: BranchAction,
+}
+
+impl Plan {
+    pub fn new() -> Self {
+        Self {
+            version: PLAN_VERSION,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Adds the default action the cleaner would propose for each branch that needs one.
+    pub fn add_branches(
+        &mut self,
+        repo: &GitRepo,
+        branches: &[Branch],
+        default_actions: &DefaultActions,
+    ) {
+        for branch in branches {
+            let proposal = propose(repo, branch, default_actions);
+            if let Some(action) = proposal.default_action() {
+                self.entries.push(PlanEntry {
+                    repo: repo.dir().to_path_buf(),
+                    branch: branch.refname.clone(),
+                    tip: branch.tip.clone(),
+                    status: branch_status(branch),
+                    message: proposal.message(),
+                    action,
+                });
+            }
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read plan {}", path.display()))?;
+        let plan: Plan = serde_json::from_str(&contents)
+            .with_context(|| format!("failed to parse plan {}", path.display()))?;
+        if plan.version != PLAN_VERSION {
+            bail!(
+                "plan {} has version {}, but only version {} is supported",
+                path.display(),
+                plan.version,
+                PLAN_VERSION
+            );
+        }
+        Ok(plan)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(path, contents + "\n")
+            .with_context(|| format!("failed to write plan {}", path.display()))
+    }
+
+    /// Carries out every entry, re-reading each repository first and skipping branches
+    /// that have moved or changed status since the plan was made.
+    pub fn apply(
+        &self,
+        cleaner: &GitCleaner<NoPrompt>,
+        backend: &SharedBackend,
+        settings: &Settings,
+    ) -> Vec<(PlanEntry, Outcome)> {
+        let mut outcomes = Vec::new();
+        for repo_dir in self.repo_dirs() {
+            let repo = settings.repo(repo_dir.clone(), backend.clone());
+            let entries = self.entries.iter().filter(|entry| entry.repo == repo_dir);
+            match settings.read_branches(&repo) {
+                Ok(branches) => {
+                    for entry in entries {
+                        let outcome = apply_entry(cleaner, &repo, &branches, entry);
+                        outcomes.push((entry.clone(), outcome));
+                    }
+                }
+                Err(error) => {
+                    for entry in entries {
+                        outcomes.push((entry.clone(), Outcome::Failed(format!("{error:#}"))));
+                    }
+                }
+            }
+        }
+        outcomes
+    }
+
+    fn repo_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        for entry in &self.entries {
+            if !dirs.contains(&entry.repo) {
+                dirs.push(entry.repo.clone());
+            }
+        }
+        dirs
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Applied,
+    Skipped(String),
+    Failed(String),
+}
+
+fn apply_entry(
+    cleaner: &GitCleaner<NoPrompt>,
+    repo: &GitRepo,
+    branches: &[Branch],
+    entry: &PlanEntry,
+) -> Outcome {
+    let Some(branch) = branches
+        .iter()
+        .find(|branch| branch.refname == entry.branch)
+    else {
+        return Outcome::Skipped("branch no longer exists".to_string());
+    };
+    if branch.tip != entry.tip {
+        return Outcome::Skipped(format!(
+            "branch has moved from {} to {}",
+            short(&entry.tip),
+            short(&branch.tip)
+        ));
+    }
+    let status = branch_status(branch);
+    if status != entry.status {
+        return Outcome::Skipped(format!(
+            "status changed from {} to {}",
+            entry.status.label(),
+            status.label()
+        ));
+    }
+    if branch.protected && entry.action.is_destructive_for(branch) {
+        return Outcome::Skipped(format!(
+            "branch is protected against \"{}\"",
+            entry.action.description()
+        ));
+    }
+    match entry.action {
+        BranchAction::Nothing => return Outcome::Skipped("nothing to do".to_string()),
+        BranchAction::Log | BranchAction::Shell | BranchAction::OpenPr => {
+            return Outcome::Skipped(format!(
+                "\"{}\" can't be applied non-interactively",
+                entry.action.description()
+            ));
+        }
+        _ => {}
+    }
+
+    match cleaner.perform_action(repo, branch, entry.action) {
+        Ok(ActionResult::Handled) | Ok(ActionResult::NotHandled) => Outcome::Applied,
+        Ok(ActionResult::ExitToShell(path)) => {
+            Outcome::Skipped(format!("needs to be handled in {}", path.display()))
+        }
+        Err(error) => Outcome::Failed(format!("{error:#}")),
+    }
+}
+
+fn short(commit: &str) -> &str {
+    &commit[..commit.len().min(7)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::backend::ProcessBackend;
+    use std::process::Command;
+    use tempfile::tempdir;
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .env("GIT_AUTHOR_NAME", "Test")
+            .env("GIT_AUTHOR_EMAIL", "test@example.com")
+            .env("GIT_COMMITTER_NAME", "Test")
+            .env("GIT_COMMITTER_EMAIL", "test@example.com")
+            .output()
+            .expect("failed to run git");
+        assert!(output.status.success(), "git {args:?} failed");
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    fn repo_with_feature_branch(dir: &Path) -> String {
+        git(dir, &["init", "-q", "-b", "main"]);
+        git(dir, &["config", "branch-assistant.defaultBranch", "main"]);
+        git(dir, &["commit", "-q", "--allow-empty", "-m", "initial"]);
+        git(dir, &["checkout", "-q", "-b", "feature"]);
+        git(dir, &["commit", "-q", "--allow-empty", "-m", "on feature"]);
+        git(dir, &["checkout", "-q", "main"]);
+        git(
+            dir,
+            &["merge", "-q", "--no-ff", "-m", "merge feature", "feature"],
+        );
+        git(dir, &["rev-parse", "feature"])
+    }
+
+    fn delete_feature(dir: &Path, tip: String) -> Plan {
+        Plan {
+            version: PLAN_VERSION,
+            entries: vec![PlanEntry {
+                repo: dir.to_path_buf(),
+                branch: "feature".into(),
+                tip,
+                status: BranchStatus::Merged,
+                message: "Branch has no upstream".into(),
+                action: BranchAction::DeleteMerged,
+            }],
+        }
+    }
+
+    #[test]
+    fn plan_round_trips_through_file() -> Result<()> {
+        let temp = tempdir()?;
+        let path = temp.path().join("plan.json");
+        let plan = delete_feature(temp.path(), "abc1234".into());
+        plan.write(&path)?;
+        assert_eq!(Plan::read(&path)?, plan);
+        assert!(fs::read_to_string(&path)?.contains("\"action\": \"DeleteMerged\""));
+        Ok(())
+    }
+
+    #[test]
+    fn apply_performs_action_when_branch_is_unchanged() -> Result<()> {
+        let temp = tempdir()?;
+        let tip = repo_with_feature_branch(temp.path());
+
+        let outcomes = delete_feature(temp.path(), tip).apply(
+            &GitCleaner::new(NoPrompt),
+            &ProcessBackend::shared(),
+            &Settings::default(),
+        );
+
+        assert_eq!(outcomes[0].1, Outcome::Applied);
+        assert_eq!(git(temp.path(), &["branch", "--list", "feature"]), "");
+        Ok(())
+    }
+
+    #[test]
+    fn apply_skips_branch_that_has_moved() -> Result<()> {
+        let temp = tempdir()?;
+        let tip = repo_with_feature_branch(temp.path());
+        git(temp.path(), &["checkout", "-q", "feature"]);
+        git(
+            temp.path(),
+            &["commit", "-q", "--allow-empty", "-m", "more"],
+        );
+
+        let outcomes = delete_feature(temp.path(), tip).apply(
+            &GitCleaner::new(NoPrompt),
+            &ProcessBackend::shared(),
+            &Settings::default(),
+        );
+
+        assert!(matches!(&outcomes[0].1, Outcome::Skipped(reason) if reason.contains("moved")));
+        assert_eq!(
+            git(temp.path(), &["branch", "--list", "feature"]),
+            "* feature"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+/// Glob patterns for branches the cleaner must never delete or rewrite, such as
+/// `main` or `release/*`.
+///
+/// `*` matches any run of characters within one path component, `**` also matches
+/// across `/`, and `?` matches a single character other than `/`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProtectedBranches {
+    patterns: Vec<String>,
+}
+
+impl ProtectedBranches {
+    pub fn new(patterns: Vec<String>) -> Self {
+        Self { patterns }
+    }
+
+    /// These patterns together with `more`, such as a repository's own.
+    pub fn with(&self, more: Vec<String>) -> Self {
+        Self::new(self.patterns.iter().cloned().chain(more).collect())
+    }
+
+    pub fn matches(&self, branch: &str) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| glob_match(pattern, branch))
+    }
+}
+
+/// Whether `text` matches `pattern`, with the wildcards described above.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    glob_matches(pattern.as_bytes(), text.as_bytes())
+}
+
+fn glob_matches(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', rest @ ..] => (0..=text.len()).any(|skip| glob_matches(rest, &text[skip..])),
+        [b'*', rest @ ..] => {
+            let component = text.iter().position(|&c| c == b'/').unwrap_or(text.len());
+            (0..=component).any(|skip| glob_matches(rest, &text[skip..]))
+        }
+        [b'?', rest @ ..] => match text {
+            [c, text @ ..] if *c != b'/' => glob_matches(rest, text),
+            _ => false,
+        },
+        [p, rest @ ..] => match text {
+            [c, text @ ..] if c == p => glob_matches(rest, text),
+            _ => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn protected(patterns: &[&str]) -> ProtectedBranches {
+        ProtectedBranches::new(patterns.iter().map(|p| p.to_string()).collect())
+    }
+
+    #[test]
+    fn matches_exact_names_and_globs() {
+        let protected = protected(&["main", "release/*", "hotfix-?", "env/**"]);
+        assert!(protected.matches("main"));
+        assert!(!protected.matches("main2"));
+        assert!(protected.matches("release/1.0"));
+        assert!(!protected.matches("release/1.0/fix"));
+        assert!(!protected.matches("release"));
+        assert!(protected.matches("hotfix-1"));
+        assert!(!protected.matches("hotfix-12"));
+        assert!(protected.matches("env/prod/eu"));
+        assert!(!protected.matches("feature"));
+    }
+
+    #[test]
+    fn nothing_is_protected_by_default() {
+        assert!(!ProtectedBranches::default().matches("main"));
+        assert!(
+            ProtectedBranches::default()
+                .with(vec!["prod".into()])
+                .matches("prod")
+        );
+    }
+}
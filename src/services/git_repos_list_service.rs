@@ -1,16 +1,24 @@
+use std::cmp::Ordering;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 use std::thread;
 
-use anyhow::Result;
+use anyhow::{Result, bail};
+use clap::ValueEnum;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 
+use crate::branch_filter::BranchFilter;
 use crate::cache::BranchCache;
-use crate::fs_utils::is_globally_ignored;
+use crate::discovery::Discovered;
+use crate::forge::PullRequest;
+use crate::git::backend::SharedBackend;
 use crate::git::{Branch, GitRepo, UpstreamStatus};
+use crate::output::{OutputFormat, OutputWriter, Record};
 use crate::picker::{self, PickerOutcome};
+use crate::services::git_repos_service::{GitResult, Problems, print_problems};
+use crate::settings::Settings;
 use crate::task_result::TaskResult;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,13 +27,34 @@ pub struct BranchListEntry {
     pub repo_path: PathBuf,
     pub refname: String,
     pub status: BranchStatus,
+    #[serde(default)]
+    pub ahead: u32,
+    #[serde(default)]
+    pub behind: u32,
     pub commit_timestamp: i64,
     pub commit_date: String,
     pub committer: String,
+    #[serde(default)]
+    pub committer_email: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub author_email: String,
+    #[serde(default)]
+    pub author_timestamp: i64,
+    #[serde(default)]
+    pub author_date: String,
+    /// The subject of the latest commit.
+    #[serde(default)]
+    pub subject: String,
     pub worktree_path: Option<PathBuf>,
+    #[serde(default)]
+    pub protected: bool,
+    #[serde(default)]
+    pub pull_request: Option<PullRequest>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum BranchStatus {
     Identical,
     UpstreamAhead,
@@ -33,6 +62,7 @@ pub enum BranchStatus {
     Diverged,
     UpstreamGone,
     NoUpstream,
+    Merged,
 }
 
 impl BranchStatus {
@@ -44,131 +74,420 @@ impl BranchStatus {
             Self::Diverged => "diverged",
             Self::UpstreamGone => "gone",
             Self::NoUpstream => "no upstream",
+            Self::Merged => "merged",
+        }
+    }
+}
+
+/// A column of the `repos --list` listing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Column {
+    /// The date of the latest commit.
+    Date,
+    Status,
+    /// Commits ahead of and behind the upstream, such as `+3/-1`.
+    Counts,
+    /// Left out when no branch has a pull request.
+    PullRequest,
+    Committer,
+    CommitterEmail,
+    /// Who wrote the latest commit, which doesn't change when the branch is rebased.
+    Author,
+    AuthorEmail,
+    AuthorDate,
+    /// The subject of the latest commit.
+    Subject,
+    /// The repo and branch, such as `repo-a/feature`.
+    Branch,
+}
+
+/// Subjects longer than this are cut short in the listing.
+const SUBJECT_WIDTH: usize = 50;
+
+impl Column {
+    pub const DEFAULT: [Column; 6] = [
+        Column::Date,
+        Column::Status,
+        Column::Counts,
+        Column::PullRequest,
+        Column::Committer,
+        Column::Branch,
+    ];
+
+    fn cell(&self, entry: &BranchListEntry) -> String {
+        match self {
+            Self::Date => entry.commit_date.clone(),
+            Self::Status => entry.status.label().to_string(),
+            Self::Counts => format_counts(entry),
+            Self::PullRequest => format_pull_request(entry),
+            Self::Committer => entry.committer.clone(),
+            Self::CommitterEmail => entry.committer_email.clone(),
+            Self::Author => entry.author.clone(),
+            Self::AuthorEmail => entry.author_email.clone(),
+            Self::AuthorDate => entry.author_date.clone(),
+            Self::Subject => truncate(&entry.subject, SUBJECT_WIDTH),
+            Self::Branch => format_location(entry),
+        }
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else {
+        let cut: String = text.chars().take(width - 1).collect();
+        format!("{cut}\u{2026}")
+    }
+}
+
+/// What `repos --list` orders branches by.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    /// The date of the latest commit, oldest first.
+    #[default]
+    Date,
+    Repo,
+    Branch,
+    Status,
+    Committer,
+    /// Commits ahead of the upstream, fewest first.
+    Ahead,
+    /// Commits behind the upstream, fewest first.
+    Behind,
+}
+
+/// What `repos --list` puts branches under headers by.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    #[default]
+    None,
+    Repo,
+    Status,
+}
+
+impl GroupBy {
+    /// The header `entry` goes under, if grouping.
+    fn header(&self, entry: &BranchListEntry) -> Option<String> {
+        match self {
+            Self::None => None,
+            Self::Repo => Some(entry.repo_name.clone()),
+            Self::Status => Some(entry.status.label().to_string()),
+        }
+    }
+
+    fn compare(&self, a: &BranchListEntry, b: &BranchListEntry) -> Ordering {
+        match self {
+            Self::None => Ordering::Equal,
+            Self::Repo => a.repo_name.cmp(&b.repo_name),
+            Self::Status => a.status.cmp(&b.status),
+        }
+    }
+}
+
+/// How `repos --list` arranges branches: grouped first, if at all, then sorted within
+/// each group.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ListOrder {
+    pub sort: SortKey,
+    pub reverse: bool,
+    pub group_by: GroupBy,
+}
+
+impl ListOrder {
+    pub fn sort(&self, entries: &mut [BranchListEntry]) {
+        entries.sort_by(|a, b| self.group_by.compare(a, b).then_with(|| self.compare(a, b)));
+    }
+
+    fn compare(&self, a: &BranchListEntry, b: &BranchListEntry) -> Ordering {
+        let by_key = match self.sort {
+            SortKey::Date => Ordering::Equal,
+            SortKey::Repo => a.repo_name.cmp(&b.repo_name),
+            SortKey::Branch => a.refname.cmp(&b.refname),
+            SortKey::Status => a.status.cmp(&b.status),
+            SortKey::Committer => a.committer.to_lowercase().cmp(&b.committer.to_lowercase()),
+            SortKey::Ahead => a.ahead.cmp(&b.ahead),
+            SortKey::Behind => a.behind.cmp(&b.behind),
+        }
+        .then_with(|| oldest_first(a, b));
+        if self.reverse {
+            by_key.reverse()
+        } else {
+            by_key
+        }
+    }
+}
+
+fn oldest_first(a: &BranchListEntry, b: &BranchListEntry) -> Ordering {
+    a.commit_timestamp
+        .cmp(&b.commit_timestamp)
+        .then_with(|| a.repo_name.cmp(&b.repo_name))
+        .then_with(|| a.refname.cmp(&b.refname))
+}
+
+/// Which branches `repos --list` shows, and how.
+#[derive(Debug, Clone)]
+pub struct ListView {
+    pub filter: BranchFilter,
+    pub order: ListOrder,
+    pub columns: Vec<Column>,
+}
+
+impl Default for ListView {
+    fn default() -> Self {
+        Self {
+            filter: BranchFilter::default(),
+            order: ListOrder::default(),
+            columns: Column::DEFAULT.to_vec(),
         }
     }
 }
 
 pub struct GitReposListService {
     interactive: bool,
+    output: OutputFormat,
+    columns: Vec<Column>,
+    scanner: BranchScanner,
 }
 
 impl GitReposListService {
-    pub fn new(interactive: bool) -> Self {
-        Self { interactive }
+    pub fn new(interactive: bool, settings: Settings, backend: SharedBackend) -> Self {
+        Self {
+            interactive,
+            output: OutputFormat::default(),
+            columns: Column::DEFAULT.to_vec(),
+            scanner: BranchScanner {
+                settings,
+                backend,
+                filter: BranchFilter::default(),
+                order: ListOrder::default(),
+            },
+        }
+    }
+
+    pub fn with_output(mut self, output: OutputFormat) -> Self {
+        self.output = output;
+        self
+    }
+
+    pub fn with_view(mut self, view: ListView) -> Self {
+        self.scanner.filter = view.filter;
+        self.scanner.order = view.order;
+        self.columns = view.columns;
+        self
     }
 
-    pub fn list_all_branches(&self, path: &Path) -> Result<TaskResult> {
+    pub fn list_all_branches(&self, roots: &[PathBuf]) -> Result<TaskResult> {
         if self.interactive && picker::stderr_is_terminal() {
-            self.run_interactive(path)
+            self.run_interactive(roots)
         } else {
-            self.run_non_interactive(path)
+            self.run_non_interactive(roots)
         }
     }
 
-    fn run_non_interactive(&self, path: &Path) -> Result<TaskResult> {
+    fn cache(&self) -> Option<BranchCache> {
+        BranchCache::from_env().map(|cache| cache.with_ttl(self.scanner.settings.cache_ttl_secs))
+    }
+
+    fn run_non_interactive(&self, roots: &[PathBuf]) -> Result<TaskResult> {
         eprintln!("Collecting branches...");
-        let entries = collect_and_sort(path)?;
-        if let Some(cache) = BranchCache::from_env() {
-            let _ = cache.write(path, &entries);
+        let (entries, problems) = self.scanner.collect_and_sort(roots)?;
+        if let Some(cache) = self.cache() {
+            let _ = cache.write(roots, &entries);
+        }
+        let entries = self.scanner.arrange(entries);
+        match OutputWriter::new(self.output) {
+            Some(mut writer) => {
+                for entry in entries {
+                    writer.emit(Record::Branch(Box::new(entry)))?;
+                }
+                for (path, problem) in problems {
+                    writer.emit(Record::Problem { path, problem })?;
+                }
+                writer.finish()?;
+            }
+            None => {
+                print_entries(&entries, self.scanner.order.group_by, &self.columns);
+                print_problems(&problems);
+            }
         }
-        print_entries(&entries);
         Ok(TaskResult::Proceed)
     }
 
-    fn run_interactive(&self, path: &Path) -> Result<TaskResult> {
-        let cache = BranchCache::from_env();
-        let cached = cache.as_ref().and_then(|c| c.read_fresh(path));
+    fn run_interactive(&self, roots: &[PathBuf]) -> Result<TaskResult> {
+        let cache = self.cache();
+        let cached = cache.as_ref().and_then(|c| c.read_fresh(roots));
 
+        let mut problems = Vec::new();
         let (initial, refresh_rx) = match cached {
             Some(cache_entries) => {
                 let (tx, rx) = mpsc::channel();
-                let scan_path = path.to_path_buf();
+                let scan_roots = roots.to_vec();
+                let scanner = self.scanner.clone();
                 thread::spawn(move || {
-                    let entries = collect_and_sort(&scan_path).unwrap_or_default();
-                    if let Some(cache) = BranchCache::from_env() {
-                        let _ = cache.write(&scan_path, &entries);
+                    let (entries, _) = scanner.collect_and_sort(&scan_roots).unwrap_or_default();
+                    if let Some(cache) = cache {
+                        let _ = cache.write(&scan_roots, &entries);
                     }
-                    let _ = tx.send(entries);
+                    let _ = tx.send(scanner.arrange(entries));
                 });
-                (cache_entries, Some(rx))
+                (self.scanner.arrange(cache_entries), Some(rx))
             }
             None => {
                 eprintln!("Collecting branches...");
-                let entries = collect_and_sort(path)?;
+                let (entries, scan_problems) = self.scanner.collect_and_sort(roots)?;
                 if let Some(cache) = cache {
-                    let _ = cache.write(path, &entries);
+                    let _ = cache.write(roots, &entries);
                 }
-                (entries, None)
+                problems = scan_problems;
+                (self.scanner.arrange(entries), None)
             }
         };
 
         if initial.is_empty() && refresh_rx.is_none() {
             println!("No branches found.");
+            print_problems(&problems);
             return Ok(TaskResult::Proceed);
         }
 
-        match picker::run(initial, refresh_rx)? {
-            PickerOutcome::Picked(entry) => select_entry(&entry),
+        let outcome = picker::run(
+            initial,
+            refresh_rx,
+            self.scanner.order.group_by,
+            self.columns.clone(),
+        )?;
+        print_problems(&problems);
+        match outcome {
+            PickerOutcome::Picked(entry) => select_entry(&entry, &self.scanner.backend),
             PickerOutcome::Cancelled => Ok(TaskResult::Proceed),
         }
     }
 }
 
-fn collect_and_sort(path: &Path) -> Result<Vec<BranchListEntry>> {
-    let mut entries = collect_branch_entries(path)?;
-    entries.sort_by(|a, b| {
-        a.commit_timestamp
-            .cmp(&b.commit_timestamp)
-            .then_with(|| a.repo_name.cmp(&b.repo_name))
-            .then_with(|| a.refname.cmp(&b.refname))
-    });
-    Ok(entries)
+/// Collects branch entries from every repository in the workspace roots.
+#[derive(Clone)]
+struct BranchScanner {
+    settings: Settings,
+    backend: SharedBackend,
+    /// Applied after scanning, so that the cache always holds every branch.
+    filter: BranchFilter,
+    order: ListOrder,
 }
 
-fn collect_branch_entries(path: &Path) -> Result<Vec<BranchListEntry>> {
-    let dir_paths: Vec<PathBuf> = fs::read_dir(path)?
-        .filter_map(|entry| entry.ok().map(|e| e.path()))
-        .filter(|p| p.is_dir() && !is_globally_ignored(p))
-        .collect();
+impl BranchScanner {
+    /// The entries to show, filtered and in order.
+    fn arrange(&self, entries: Vec<BranchListEntry>) -> Vec<BranchListEntry> {
+        let mut entries = self.filter.apply(entries, &self.backend);
+        self.order.sort(&mut entries);
+        entries
+    }
 
-    let entries: Vec<BranchListEntry> = dir_paths
-        .par_iter()
-        .flat_map(|entry_path| collect_repo_entries(entry_path).unwrap_or_default())
-        .collect();
+    /// The branches of every repository, oldest first, along with the repositories
+    /// that couldn't be read.
+    fn collect_and_sort(&self, roots: &[PathBuf]) -> Result<(Vec<BranchListEntry>, Problems)> {
+        let dir_paths: Vec<PathBuf> = self
+            .settings
+            .discovery
+            .discover(roots)?
+            .into_iter()
+            .filter_map(|found| match found {
+                Discovered::Directory(path) => Some(path),
+                Discovered::NotDirectory(_) => None,
+            })
+            .collect();
 
-    Ok(entries)
-}
+        let results: Vec<(PathBuf, Result<Vec<BranchListEntry>>)> = dir_paths
+            .par_iter()
+            .map(|entry_path| {
+                let result = self.collect_repo_entries(entry_path, roots);
+                (entry_path.clone(), result)
+            })
+            .collect();
 
-fn collect_repo_entries(entry_path: &Path) -> Result<Vec<BranchListEntry>> {
-    let repo = GitRepo::new(entry_path.to_path_buf());
-    let branches = repo.get_branches()?;
-    let commit_infos = repo.branch_commit_infos()?;
-    let repo_name = entry_path
-        .file_name()
-        .and_then(|name| name.to_str())
-        .map(|name| name.to_string())
-        .unwrap_or_else(|| entry_path.to_string_lossy().into_owned());
-
-    let mut entries = Vec::new();
-    for branch in branches {
-        let Some(info) = commit_infos.get(&branch.refname) else {
-            continue;
-        };
-        entries.push(BranchListEntry {
-            repo_name: repo_name.clone(),
-            repo_path: entry_path.to_path_buf(),
-            refname: branch.refname.clone(),
-            status: branch_status(&branch),
-            commit_timestamp: info.commit_timestamp,
-            commit_date: info.commit_date.clone(),
-            committer: info.committer.clone(),
-            worktree_path: branch.worktree_path.clone(),
-        });
+        let mut entries = Vec::new();
+        let mut problems = Vec::new();
+        for (path, result) in results {
+            match result {
+                Ok(repo_entries) => entries.extend(repo_entries),
+                Err(error) => {
+                    if let Some(problem) = GitResult::from_error(&error).problem() {
+                        problems.push((path, problem));
+                    }
+                }
+            }
+        }
+        problems.sort();
+
+        entries.sort_by(oldest_first);
+        Ok((entries, problems))
+    }
+
+    fn collect_repo_entries(
+        &self,
+        entry_path: &Path,
+        roots: &[PathBuf],
+    ) -> Result<Vec<BranchListEntry>> {
+        let repo = self
+            .settings
+            .repo(entry_path.to_path_buf(), self.backend.clone());
+        if !paths_equivalent(&repo.toplevel()?, entry_path) {
+            bail!("not a git repository");
+        }
+        if repo.config().skip {
+            return Ok(Vec::new());
+        }
+        let branches = self.settings.read_branches(&repo)?;
+        let commit_infos = repo.branch_commit_infos()?;
+        let repo_name = repo_name(entry_path, roots);
+
+        let mut entries = Vec::new();
+        for branch in branches {
+            let Some(info) = commit_infos.get(&branch.refname) else {
+                continue;
+            };
+            entries.push(BranchListEntry {
+                repo_name: repo_name.clone(),
+                repo_path: entry_path.to_path_buf(),
+                refname: branch.refname.clone(),
+                status: branch_status(&branch),
+                ahead: branch.upstream.as_ref().map_or(0, |u| u.ahead),
+                behind: branch.upstream.as_ref().map_or(0, |u| u.behind),
+                commit_timestamp: info.commit_timestamp,
+                commit_date: info.commit_date.clone(),
+                committer: info.committer.clone(),
+                committer_email: info.committer_email.clone(),
+                author: info.author.clone(),
+                author_email: info.author_email.clone(),
+                author_timestamp: info.author_timestamp,
+                author_date: info.author_date.clone(),
+                subject: info.subject.clone(),
+                worktree_path: branch.worktree_path.clone(),
+                protected: branch.protected,
+                pull_request: branch.pull_request.clone(),
+            });
+        }
+        Ok(entries)
     }
-    Ok(entries)
 }
 
-fn branch_status(branch: &Branch) -> BranchStatus {
+/// The repository's path below the workspace root it was found in, such as
+/// `org/repo`.
+fn repo_name(path: &Path, roots: &[PathBuf]) -> String {
+    roots
+        .iter()
+        .find_map(|root| path.strip_prefix(root).ok())
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .unwrap_or(path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+pub fn branch_status(branch: &Branch) -> BranchStatus {
+    if branch.merged_into.is_some() {
+        return BranchStatus::Merged;
+    }
     match branch.upstream.as_ref() {
         None => BranchStatus::NoUpstream,
         Some(upstream) => match upstream.status {
@@ -181,50 +500,109 @@ fn branch_status(branch: &Branch) -> BranchStatus {
     }
 }
 
-fn print_entries(entries: &[BranchListEntry]) {
-    for line in format_entry_lines(entries) {
-        println!("{line}");
+fn print_entries(entries: &[BranchListEntry], group_by: GroupBy, columns: &[Column]) {
+    for (index, row) in format_rows(entries, group_by, columns)
+        .into_iter()
+        .enumerate()
+    {
+        match row {
+            ListRow::Header(header) if index == 0 => println!("{header}"),
+            ListRow::Header(header) => println!("\n{header}"),
+            ListRow::Entry(_, line) => println!("{line}"),
+        }
     }
 }
 
-pub fn format_entry_lines(entries: &[BranchListEntry]) -> Vec<String> {
+/// A line of the listing: a group's header, or the entry at an index.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ListRow {
+    Header(String),
+    Entry(usize, String),
+}
+
+/// The entries' lines, with a header before each group when grouping. The entries must
+/// already be sorted into their groups.
+pub fn format_rows(
+    entries: &[BranchListEntry],
+    group_by: GroupBy,
+    columns: &[Column],
+) -> Vec<ListRow> {
+    let mut rows = Vec::new();
+    let mut current = None;
+    for (index, (entry, line)) in entries
+        .iter()
+        .zip(format_entry_lines(entries, columns))
+        .enumerate()
+    {
+        if let Some(header) = group_by.header(entry)
+            && current.as_ref() != Some(&header)
+        {
+            rows.push(ListRow::Header(format!("{header}:")));
+            current = Some(header);
+        }
+        rows.push(ListRow::Entry(index, line));
+    }
+    rows
+}
+
+pub fn format_entry_lines(entries: &[BranchListEntry], columns: &[Column]) -> Vec<String> {
     if entries.is_empty() {
         return Vec::new();
     }
-    let status_width = entries
-        .iter()
-        .map(|entry| entry.status.label().len())
-        .max()
-        .unwrap_or(0);
-    let committer_width = entries
+    let cells: Vec<Vec<String>> = columns
         .iter()
-        .map(|entry| entry.committer.chars().count())
-        .max()
-        .unwrap_or(0);
-    let location_width = entries
-        .iter()
-        .map(|entry| entry.repo_name.chars().count() + 1 + entry.refname.chars().count())
-        .max()
-        .unwrap_or(0);
-    entries
+        .map(|column| entries.iter().map(|entry| column.cell(entry)).collect())
+        .collect();
+    // The pull request column is left out entirely when no branch has one.
+    let shown: Vec<(&Vec<String>, usize)> = columns
         .iter()
-        .map(|entry| {
-            let location = format!("{}/{}", entry.repo_name, entry.refname);
-            format!(
-                "{date}  {status:<status_width$}  {committer:<committer_width$}  {location:<location_width$}",
-                date = entry.commit_date,
-                status = entry.status.label(),
-                committer = entry.committer,
-                location = location,
-                status_width = status_width,
-                committer_width = committer_width,
-                location_width = location_width,
-            )
+        .zip(&cells)
+        .map(|(column, cells)| {
+            let width = cells.iter().map(|cell| cell.chars().count()).max();
+            (column, cells, width.unwrap_or(0))
+        })
+        .filter(|(column, _, width)| **column != Column::PullRequest || *width > 0)
+        .map(|(_, cells, width)| (cells, width))
+        .collect();
+    (0..entries.len())
+        .map(|row| {
+            shown
+                .iter()
+                .map(|(cells, width)| format!("{:<width$}", cells[row], width = *width))
+                .collect::<Vec<_>>()
+                .join("  ")
         })
         .collect()
 }
 
-fn select_entry(entry: &BranchListEntry) -> Result<TaskResult> {
+fn format_location(entry: &BranchListEntry) -> String {
+    if entry.protected {
+        format!("{}/{} [protected]", entry.repo_name, entry.refname)
+    } else {
+        format!("{}/{}", entry.repo_name, entry.refname)
+    }
+}
+
+/// Ahead/behind counts relative to the upstream, e.g. `+3/-1`. Blank for branches
+/// without a live upstream to compare against.
+fn format_counts(entry: &BranchListEntry) -> String {
+    match entry.status {
+        BranchStatus::NoUpstream | BranchStatus::UpstreamGone => String::new(),
+        _ if entry.ahead == 0 && entry.behind == 0 => String::new(),
+        _ => format!("+{}/-{}", entry.ahead, entry.behind),
+    }
+}
+
+/// The branch's pull request, e.g. `#12 open, approved`. Blank when it has none.
+fn format_pull_request(entry: &BranchListEntry) -> String {
+    entry
+        .pull_request
+        .as_ref()
+        .map(PullRequest::summary)
+        .unwrap_or_default()
+}
+
+fn select_entry(entry: &BranchListEntry, backend: &SharedBackend) -> Result<TaskResult> {
     if let Some(worktree_path) = &entry.worktree_path
         && !paths_equivalent(worktree_path, &entry.repo_path)
     {
@@ -235,7 +613,7 @@ fn select_entry(entry: &BranchListEntry) -> Result<TaskResult> {
         );
         return Ok(TaskResult::ShellActionRequired(worktree_path.clone()));
     }
-    let repo = GitRepo::new(entry.repo_path.clone());
+    let repo = GitRepo::with_backend(entry.repo_path.clone(), backend.clone());
     repo.checkout_branch(&entry.refname)?;
     Ok(TaskResult::ShellActionRequired(entry.repo_path.clone()))
 }
@@ -250,6 +628,8 @@ fn paths_equivalent(a: &Path, b: &Path) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::forge::{PullRequestState, ReviewStatus};
+    use crate::git::backend::ProcessBackend;
     use anyhow::Result;
 
     fn entry(timestamp: i64, repo: &str, refname: &str) -> BranchListEntry {
@@ -258,13 +638,89 @@ mod tests {
             repo_path: PathBuf::from("/tmp").join(repo),
             refname: refname.to_string(),
             status: BranchStatus::Identical,
+            ahead: 0,
+            behind: 0,
             commit_timestamp: timestamp,
             commit_date: "2024-01-01".to_string(),
             committer: "alice".to_string(),
+            committer_email: "alice@example.com".to_string(),
+            author: "alice".to_string(),
+            author_email: "alice@example.com".to_string(),
+            author_timestamp: 1000,
+            author_date: "2024-01-01".to_string(),
+            subject: "Add feature".to_string(),
             worktree_path: None,
+            protected: false,
+            pull_request: None,
         }
     }
 
+    #[test]
+    fn format_entry_lines_shows_ahead_behind_counts() {
+        let mut diverged = entry(1000, "repo", "feature");
+        diverged.status = BranchStatus::Diverged;
+        diverged.ahead = 3;
+        diverged.behind = 1;
+        let mut local_only = entry(2000, "repo", "new");
+        local_only.status = BranchStatus::NoUpstream;
+
+        let lines = format_entry_lines(&[diverged, local_only], &Column::DEFAULT);
+        assert_eq!(
+            lines[0],
+            "2024-01-01  diverged     +3/-1  alice  repo/feature"
+        );
+        assert_eq!(
+            lines[1],
+            "2024-01-01  no upstream         alice  repo/new    "
+        );
+    }
+
+    #[test]
+    fn format_entry_lines_marks_protected_branches() {
+        let mut main = entry(1000, "repo", "main");
+        main.protected = true;
+        let lines = format_entry_lines(&[main, entry(2000, "repo", "feature")], &Column::DEFAULT);
+        assert_eq!(lines[0], "2024-01-01  ok    alice  repo/main [protected]");
+        assert_eq!(lines[1], "2024-01-01  ok    alice  repo/feature         ");
+    }
+
+    #[test]
+    fn format_entry_lines_shows_pull_requests() {
+        let mut feature = entry(1000, "repo", "feature");
+        feature.pull_request = Some(PullRequest {
+            number: 12,
+            state: PullRequestState::Open,
+            url: "https://github.com/o/r/pull/12".to_string(),
+            review: Some(ReviewStatus::Approved),
+            head_sha: None,
+        });
+        let lines = format_entry_lines(&[feature, entry(2000, "repo", "main")], &Column::DEFAULT);
+        assert_eq!(
+            lines[0],
+            "2024-01-01  ok    #12 open, approved  alice  repo/feature"
+        );
+        assert_eq!(
+            lines[1],
+            "2024-01-01  ok                        alice  repo/main   "
+        );
+    }
+
+    #[test]
+    fn format_entry_lines_shows_selected_columns() {
+        let mut rebased = entry(1000, "repo", "feature");
+        rebased.committer = "bob".to_string();
+        rebased.subject = "x".repeat(60);
+        let lines = format_entry_lines(
+            &[rebased, entry(2000, "repo", "main")],
+            &[Column::Author, Column::Committer, Column::Subject],
+        );
+        assert_eq!(
+            lines[0],
+            format!("alice  bob    {}\u{2026}", "x".repeat(49))
+        );
+        assert_eq!(lines[1], format!("alice  alice  {:<50}", "Add feature"));
+    }
+
     #[test]
     fn entries_sort_oldest_first() {
         let mut entries = [
@@ -272,18 +728,53 @@ mod tests {
             entry(1000, "repo-b", "main"),
             entry(3000, "repo-a", "feature"),
         ];
-        entries.sort_by(|a, b| {
-            a.commit_timestamp
-                .cmp(&b.commit_timestamp)
-                .then_with(|| a.repo_name.cmp(&b.repo_name))
-                .then_with(|| a.refname.cmp(&b.refname))
-        });
+        ListOrder::default().sort(&mut entries);
         assert_eq!(entries[0].repo_name, "repo-b");
         assert_eq!(entries[1].repo_name, "repo-a");
         assert_eq!(entries[1].refname, "main");
         assert_eq!(entries[2].refname, "feature");
     }
 
+    #[test]
+    fn entries_sort_by_key_within_groups() {
+        let mut behind = entry(3000, "repo-b", "behind");
+        behind.behind = 5;
+        let mut also_behind = entry(1000, "repo-a", "stale");
+        also_behind.behind = 2;
+        let mut entries = [
+            behind,
+            also_behind,
+            entry(2000, "repo-a", "main"),
+            entry(500, "repo-b", "main"),
+        ];
+        let order = ListOrder {
+            sort: SortKey::Behind,
+            reverse: true,
+            group_by: GroupBy::Repo,
+        };
+        order.sort(&mut entries);
+        let names: Vec<String> = entries
+            .iter()
+            .map(|entry| format!("{}/{}", entry.repo_name, entry.refname))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "repo-a/stale",
+                "repo-a/main",
+                "repo-b/behind",
+                "repo-b/main"
+            ]
+        );
+
+        let rows = format_rows(&entries, GroupBy::Repo, &Column::DEFAULT);
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[0], ListRow::Header("repo-a:".into()));
+        assert!(matches!(&rows[1], ListRow::Entry(0, line) if line.ends_with("repo-a/stale ")));
+        assert_eq!(rows[3], ListRow::Header("repo-b:".into()));
+        assert!(matches!(&rows[5], ListRow::Entry(3, _)));
+    }
+
     #[test]
     fn select_entry_redirects_to_worktree_path() -> Result<()> {
         let temp_repo = tempfile::tempdir()?;
@@ -292,7 +783,7 @@ mod tests {
         entry.repo_path = temp_repo.path().to_path_buf();
         entry.worktree_path = Some(temp_worktree.path().to_path_buf());
 
-        let result = select_entry(&entry)?;
+        let result = select_entry(&entry, &ProcessBackend::shared())?;
         match result {
             TaskResult::ShellActionRequired(path) => {
                 assert_eq!(path, temp_worktree.path().to_path_buf());
@@ -305,8 +796,9 @@ mod tests {
     #[test]
     fn non_interactive_list_proceeds() -> Result<()> {
         let temp = tempfile::tempdir()?;
-        let service = GitReposListService::new(false);
-        let result = service.list_all_branches(temp.path())?;
+        let service =
+            GitReposListService::new(false, Settings::default(), ProcessBackend::shared());
+        let result = service.list_all_branches(&[temp.path().to_path_buf()])?;
         assert!(matches!(result, TaskResult::Proceed));
         Ok(())
     }
@@ -5,27 +5,48 @@ use std::path::{Path, PathBuf};
 #[cfg(feature = "timings")]
 use std::time::Instant;
 
-use crate::cleaner::GitCleaner;
-use crate::fs_utils::is_globally_ignored;
+use crate::auto::{AutoPrompt, AutoReport};
+use crate::cleaner::{BranchOutcome, GitCleaner};
+use crate::discovery::Discovered;
+use crate::git::backend::SharedBackend;
 use crate::git::{Branch, GitRepo};
+use crate::journal::Journal;
+use crate::output::{BranchRecord, RepoRecord, RepoState};
+use crate::plan::Plan;
+use crate::settings::Settings;
 use crate::task_result::TaskResult;
-use crate::ui::{DialoguerPrompt, DryRunPrompt};
+use crate::ui::{AssumedChoice, DialoguerPrompt, DryRunPrompt};
 
 pub struct GitReposService {
-    dry_run: bool,
+    /// Set for dry runs, with the choice to assume at each prompt.
+    dry_run: Option<AssumedChoice>,
     skip_dirty_repos: bool,
+    settings: Settings,
+    backend: SharedBackend,
 }
 
 impl GitReposService {
-    pub fn new(dry_run: bool, skip_dirty_repos: bool) -> Self {
+    pub fn new(
+        dry_run: Option<AssumedChoice>,
+        skip_dirty_repos: bool,
+        settings: Settings,
+        backend: SharedBackend,
+    ) -> Self {
         Self {
             dry_run,
             skip_dirty_repos,
+            settings,
+            backend,
         }
     }
 
-    pub fn handle_all_git_repos(&self, path: &Path) -> Result<TaskResult> {
-        let results = self.fetch_all_results(path)?;
+    fn repo(&self, dir: PathBuf) -> GitRepo {
+        self.settings.repo(dir, self.backend.clone())
+    }
+
+    pub fn handle_all_git_repos(&self, roots: &[PathBuf]) -> Result<TaskResult> {
+        let results = self.fetch_all_results(roots)?;
+        let problems = problems(&results);
         let mut task_result = TaskResult::Proceed;
 
         for result in results
@@ -38,75 +59,159 @@ impl GitReposService {
             task_result = self.handle_non_clean_repo_result(result)?;
         }
 
+        print_problems(&problems);
         Ok(task_result)
     }
 
-    fn fetch_all_results(&self, path: &Path) -> Result<Vec<ResultWithPath>> {
-        let entry_paths: Vec<PathBuf> = fs::read_dir(path)?
-            .map(|entry| entry.map(|e| e.path()))
-            .collect::<std::io::Result<Vec<_>>>()?;
+    /// Analyses every repository without changing anything, collecting the action the
+    /// cleaner would propose for each branch.
+    pub fn plan_all_git_repos(&self, roots: &[PathBuf]) -> Result<Plan> {
+        let mut plan = Plan::new();
+        let results = self.fetch_all_results(roots)?;
+        let problems = problems(&results);
+        for result in results {
+            match result.result {
+                GitResult::Clean
+                | GitResult::NotGitRepository
+                | GitResult::DubiousOwnership
+                | GitResult::Error(_) => {}
+                GitResult::NotDirectory => {
+                    eprintln!("Not a directory: {}", result.path.display());
+                }
+                GitResult::BranchesNeedingAction(branches) => {
+                    plan.add_branches(
+                        &self.repo(result.path),
+                        &branches,
+                        &self.settings.default_actions,
+                    );
+                }
+            }
+        }
+        print_problems(&problems);
+        Ok(plan)
+    }
+
+    /// Deals with every repository's branches as `prompt` decides, carrying on past
+    /// anything that fails or needs a human, and reports what became of them.
+    pub fn auto_all_git_repos(&self, roots: &[PathBuf], prompt: AutoPrompt) -> Result<AutoReport> {
+        let results = self.fetch_all_results(roots)?;
+        let problems = problems(&results);
+        let cleaner = GitCleaner::new(prompt)
+            .with_journal(if self.dry_run.is_some() {
+                None
+            } else {
+                Journal::open_default()
+            })
+            .with_default_actions(self.settings.default_actions.clone())
+            .with_custom_actions(self.settings.custom_actions.clone());
+        let mut report = AutoReport::default();
+        for result in results {
+            match result.result {
+                GitResult::NotDirectory => report.add_repo(
+                    &result.path,
+                    BranchOutcome::NeedsHuman("not a directory".to_string()),
+                ),
+                GitResult::BranchesNeedingAction(branches) => {
+                    let repo = self.repo(result.path);
+                    report.add(repo.dir(), cleaner.handle_unattended(&repo, branches));
+                }
+                GitResult::Clean
+                | GitResult::NotGitRepository
+                | GitResult::DubiousOwnership
+                | GitResult::Error(_) => {}
+            }
+        }
+        print_problems(&problems);
+        Ok(report)
+    }
+
+    /// Analyses every repository without changing anything, describing what was found.
+    pub fn report_all_git_repos(&self, roots: &[PathBuf]) -> Result<Vec<RepoRecord>> {
+        let results = self.fetch_all_results(roots)?;
+        Ok(results.iter().map(ResultWithPath::record).collect())
+    }
+
+    fn fetch_all_results(&self, roots: &[PathBuf]) -> Result<Vec<ResultWithPath>> {
+        let found = self.settings.discovery.discover(roots)?;
 
-        let mut results: Vec<ResultWithPath> = entry_paths
+        let mut results: Vec<ResultWithPath> = found
             .par_iter()
-            .map(|entry_path| {
-                self.repo_result(entry_path).map(|result| ResultWithPath {
-                    path: entry_path.clone(),
-                    result,
-                })
+            .map(|found| ResultWithPath {
+                path: found.path().to_path_buf(),
+                result: self.repo_result(found),
             })
-            .collect::<Result<Vec<_>>>()?;
+            .collect();
 
         results.sort_by(|a, b| a.path.cmp(&b.path));
         Ok(results)
     }
 
-    fn repo_result(&self, dir: &Path) -> Result<GitResult> {
+    /// What needs doing in one repository. Any failure is part of the result, so that
+    /// one broken repository doesn't stop the others from being processed.
+    fn repo_result(&self, found: &Discovered) -> GitResult {
         #[cfg(feature = "timings")]
         let start = Instant::now();
 
-        let result = if !dir.is_dir() {
-            if is_globally_ignored(dir) {
-                GitResult::Clean
-            } else {
-                GitResult::NotDirectory
-            }
-        } else {
-            let repo = GitRepo::new(dir.to_path_buf());
-
-            if self.skip_dirty_repos && repo.is_dirty()? {
-                return Ok(GitResult::Clean);
-            }
-
-            let branches = repo.get_branches()?;
-            let branches_needing_action: Vec<Branch> = branches
-                .into_iter()
-                .filter(|branch| branch.needs_action())
-                .collect();
-
-            if branches_needing_action.is_empty() {
-                GitResult::Clean
-            } else {
-                GitResult::BranchesNeedingAction(branches_needing_action)
-            }
+        let result = match found {
+            Discovered::NotDirectory(_) => GitResult::NotDirectory,
+            Discovered::Directory(dir) => self
+                .directory_result(dir)
+                .unwrap_or_else(|error| GitResult::from_error(&error)),
         };
 
         #[cfg(feature = "timings")]
         {
             eprintln!(
                 "[timing] repo_result {} => {} ({:?})",
-                dir.display(),
+                found.path().display(),
                 summarize_git_result(&result),
                 start.elapsed()
             );
         }
 
-        Ok(result)
+        result
+    }
+
+    fn directory_result(&self, dir: &Path) -> Result<GitResult> {
+        let repo = self.repo(dir.to_path_buf());
+
+        // A plain directory inside another repository would otherwise be processed as
+        // that repository.
+        if !paths_equivalent(&repo.toplevel()?, dir) {
+            return Ok(GitResult::NotGitRepository);
+        }
+
+        if repo.config().skip {
+            return Ok(GitResult::Clean);
+        }
+
+        if self.skip_dirty_repos && repo.is_dirty()? {
+            return Ok(GitResult::Clean);
+        }
+
+        if self.settings.fetch {
+            repo.fetch_and_prune_reporting_errors();
+        }
+
+        let branches = repo.get_branches()?;
+        let mut branches_needing_action: Vec<Branch> = branches
+            .into_iter()
+            .filter(|branch| branch.needs_action())
+            .collect();
+        self.settings.annotate(&repo, &mut branches_needing_action);
+        repo.detect_merged_branches(&mut branches_needing_action);
+
+        if branches_needing_action.is_empty() {
+            Ok(GitResult::Clean)
+        } else {
+            Ok(GitResult::BranchesNeedingAction(branches_needing_action))
+        }
     }
 
     fn handle_non_clean_repo_result(&self, result_with_path: ResultWithPath) -> Result<TaskResult> {
         match result_with_path.result {
             GitResult::NotDirectory => {
-                if self.dry_run {
+                if self.dry_run.is_some() {
                     println!(
                         "[DRY RUN] Not a directory: {}",
                         result_with_path.path.display()
@@ -122,9 +227,12 @@ impl GitReposService {
                     Ok(TaskResult::ShellActionRequired(parent))
                 }
             }
-            GitResult::Clean => Ok(TaskResult::Proceed),
+            GitResult::Clean
+            | GitResult::NotGitRepository
+            | GitResult::DubiousOwnership
+            | GitResult::Error(_) => Ok(TaskResult::Proceed),
             GitResult::BranchesNeedingAction(branches) => {
-                if self.dry_run {
+                if self.dry_run.is_some() {
                     println!(
                         "[DRY RUN] Has branches needing action: {}",
                         result_with_path.path.display()
@@ -135,13 +243,22 @@ impl GitReposService {
                         result_with_path.path.display()
                     );
                 }
-                let repo = GitRepo::new(result_with_path.path);
-                if self.dry_run {
-                    let cleaner = GitCleaner::new_with_dry_run(DryRunPrompt::default(), true);
-                    cleaner.handle(&repo, branches)
-                } else {
-                    let cleaner = GitCleaner::new(DialoguerPrompt::default());
-                    cleaner.handle(&repo, branches)
+                let repo = self.repo(result_with_path.path);
+                match self.dry_run {
+                    Some(assumed_choice) => {
+                        let cleaner = GitCleaner::new(DryRunPrompt::new(assumed_choice))
+                            .with_default_actions(self.settings.default_actions.clone())
+                            .with_custom_actions(self.settings.custom_actions.clone());
+                        cleaner.preview(&repo, branches)?;
+                        Ok(TaskResult::Proceed)
+                    }
+                    None => {
+                        let cleaner = GitCleaner::new(DialoguerPrompt)
+                            .with_journal(Journal::open_default())
+                            .with_default_actions(self.settings.default_actions.clone())
+                            .with_custom_actions(self.settings.custom_actions.clone());
+                        cleaner.handle(&repo, branches)
+                    }
                 }
             }
         }
@@ -152,14 +269,86 @@ impl GitReposService {
 pub enum GitResult {
     Clean,
     NotDirectory,
+    NotGitRepository,
+    /// Git refuses to work in the repository because someone else owns it.
+    DubiousOwnership,
+    Error(String),
     BranchesNeedingAction(Vec<Branch>),
 }
 
+impl GitResult {
+    /// Classifies a failure to read a repository.
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        let message = format!("{error:#}");
+        if message.contains("not a git repository") {
+            GitResult::NotGitRepository
+        } else if message.contains("dubious ownership") {
+            GitResult::DubiousOwnership
+        } else {
+            GitResult::Error(message)
+        }
+    }
+
+    /// Why the repository couldn't be processed, if it couldn't.
+    pub fn problem(&self) -> Option<String> {
+        match self {
+            GitResult::NotGitRepository => Some("not a git repository".to_string()),
+            GitResult::DubiousOwnership => Some(
+                "owned by someone else; add it to git's safe.directory to work on it".to_string(),
+            ),
+            GitResult::Error(message) => Some(message.clone()),
+            GitResult::Clean | GitResult::NotDirectory | GitResult::BranchesNeedingAction(_) => {
+                None
+            }
+        }
+    }
+}
+
+/// Repositories that couldn't be processed, with the reason.
+pub type Problems = Vec<(PathBuf, String)>;
+
+fn problems(results: &[ResultWithPath]) -> Problems {
+    results
+        .iter()
+        .filter_map(|result| Some((result.path.clone(), result.result.problem()?)))
+        .collect()
+}
+
+/// Lists the repositories that couldn't be processed, after everything else is done.
+pub fn print_problems(problems: &[(PathBuf, String)]) {
+    if problems.is_empty() {
+        return;
+    }
+    eprintln!();
+    eprintln!("Could not process {}:", repositories(problems.len()));
+    for (path, problem) in problems {
+        eprintln!("  {}: {problem}", path.display());
+    }
+}
+
+fn repositories(count: usize) -> String {
+    if count == 1 {
+        "1 repository".to_string()
+    } else {
+        format!("{count} repositories")
+    }
+}
+
+fn paths_equivalent(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a_can), Ok(b_can)) => a_can == b_can,
+        _ => a == b,
+    }
+}
+
 #[cfg(feature = "timings")]
 fn summarize_git_result(result: &GitResult) -> &'static str {
     match result {
         GitResult::Clean => "Clean",
         GitResult::NotDirectory => "NotDirectory",
+        GitResult::NotGitRepository => "NotGitRepository",
+        GitResult::DubiousOwnership => "DubiousOwnership",
+        GitResult::Error(_) => "Error",
         GitResult::BranchesNeedingAction(_) => "BranchesNeedingAction",
     }
 }
@@ -168,3 +357,100 @@ struct ResultWithPath {
     path: PathBuf,
     result: GitResult,
 }
+
+impl ResultWithPath {
+    fn record(&self) -> RepoRecord {
+        let state = match &self.result {
+            GitResult::Clean => RepoState::Clean,
+            GitResult::NotDirectory => RepoState::NotDirectory,
+            GitResult::NotGitRepository => RepoState::NotGitRepository,
+            GitResult::DubiousOwnership => RepoState::DubiousOwnership,
+            GitResult::Error(_) => RepoState::Error,
+            GitResult::BranchesNeedingAction(_) => RepoState::NeedsAction,
+        };
+        let branches = match &self.result {
+            GitResult::BranchesNeedingAction(branches) => {
+                branches.iter().map(BranchRecord::from).collect()
+            }
+            _ => Vec::new(),
+        };
+        RepoRecord {
+            path: self.path.clone(),
+            state,
+            branches,
+            error: self.result.problem(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::backend::ProcessBackend;
+    use anyhow::anyhow;
+    use std::process::Command;
+    use tempfile::tempdir;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .env("GIT_AUTHOR_NAME", "Test")
+            .env("GIT_AUTHOR_EMAIL", "test@example.com")
+            .env("GIT_COMMITTER_NAME", "Test")
+            .env("GIT_COMMITTER_EMAIL", "test@example.com")
+            .status()
+            .expect("failed to run git");
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    #[test]
+    fn errors_are_classified() {
+        assert!(matches!(
+            GitResult::from_error(&anyhow!(
+                "git branch failed (stderr: fatal: not a git repository (or any of the parent directories): .git)"
+            )),
+            GitResult::NotGitRepository
+        ));
+        assert!(matches!(
+            GitResult::from_error(&anyhow!(
+                "fatal: detected dubious ownership in repository at '/srv/repo'"
+            )),
+            GitResult::DubiousOwnership
+        ));
+        assert!(matches!(
+            GitResult::from_error(&anyhow!("disk on fire")),
+            GitResult::Error(message) if message == "disk on fire"
+        ));
+        assert_eq!(GitResult::Clean.problem(), None);
+    }
+
+    #[test]
+    fn plain_directories_do_not_abort_the_scan() -> Result<()> {
+        let temp = tempdir()?;
+        let root = temp.path().canonicalize()?;
+        git(&root, &["init", "-q", "-b", "main"]);
+        fs::create_dir(root.join("plain"))?;
+        let repo = root.join("repo");
+        fs::create_dir(&repo)?;
+        git(&repo, &["init", "-q", "-b", "main"]);
+        git(&repo, &["commit", "-q", "--allow-empty", "-m", "initial"]);
+
+        let service =
+            GitReposService::new(None, false, Settings::default(), ProcessBackend::shared());
+        let results = service.fetch_all_results(std::slice::from_ref(&root))?;
+
+        let plain = results
+            .iter()
+            .find(|result| result.path.ends_with("plain"))
+            .unwrap();
+        assert!(matches!(plain.result, GitResult::NotGitRepository));
+        let repo = results
+            .iter()
+            .find(|result| result.path.ends_with("repo"))
+            .unwrap();
+        assert!(matches!(repo.result, GitResult::BranchesNeedingAction(_)));
+        assert_eq!(problems(&results).len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+
+use crate::auto::AutoRules;
+use crate::cache::PullRequestCache;
+use crate::config::{Config, CustomAction, DefaultActions, PullRequestsConfig, ToolsConfig};
+use crate::discovery::Discovery;
+use crate::forge::{PullRequest, PullRequests, head_label, remote_owner};
+use crate::git::backend::SharedBackend;
+use crate::git::{Branch, GitRepo};
+use crate::protection::ProtectedBranches;
+
+/// What every command applies to the repositories it works on, from the config file
+/// and the command line.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// Run `git fetch --prune` before reading branches.
+    pub fetch: bool,
+    /// Protected branches from the config file; each repository can add its own.
+    pub protected: ProtectedBranches,
+    /// Where branches are pushed when git doesn't say otherwise.
+    pub remote: String,
+    pub pull_requests: PullRequestsConfig,
+    pub tools: ToolsConfig,
+    pub default_actions: DefaultActions,
+    pub custom_actions: Vec<CustomAction>,
+    pub auto_rules: AutoRules,
+    /// Directories `repos` scans when no path is given.
+    pub workspace_roots: Vec<PathBuf>,
+    pub discovery: Discovery,
+    /// How long a cached `repos --list` scan or pull request lookup is used.
+    pub cache_ttl_secs: i64,
+}
+
+impl Settings {
+    pub fn from_config(config: &Config, fetch: bool) -> Self {
+        Self {
+            fetch,
+            protected: ProtectedBranches::new(config.protected.clone()),
+            remote: config.remote.clone(),
+            pull_requests: config.pull_requests.clone(),
+            tools: config.tools.clone(),
+            default_actions: config.default_actions.clone(),
+            custom_actions: config.actions.clone(),
+            auto_rules: config.auto.clone(),
+            workspace_roots: config.workspace_roots(),
+            discovery: Discovery::from_config(&config.discovery),
+            cache_ttl_secs: config.cache.ttl_secs,
+        }
+    }
+
+    pub fn repo(&self, dir: PathBuf, backend: SharedBackend) -> GitRepo {
+        GitRepo::with_backend(dir, backend)
+            .with_tools(self.tools.clone())
+            .with_pull_request_defaults(self.pull_requests.clone())
+    }
+
+    /// The directories to scan for repositories: the given path, or else the workspace
+    /// roots from the config file, or else the current directory.
+    pub fn roots(&self, path: Option<PathBuf>) -> Result<Vec<PathBuf>> {
+        let roots = match path {
+            Some(path) => vec![path],
+            None if self.workspace_roots.is_empty() => vec![env::current_dir()?],
+            None => self.workspace_roots.clone(),
+        };
+        roots
+            .into_iter()
+            .map(|root| {
+                root.canonicalize()
+                    .with_context(|| format!("failed to resolve {}", root.display()))
+            })
+            .collect()
+    }
+
+    /// All local branches, with protection and merged status filled in.
+    pub fn read_branches(&self, repo: &GitRepo) -> Result<Vec<Branch>> {
+        if self.fetch {
+            repo.fetch_and_prune_reporting_errors();
+        }
+        let mut branches = repo.get_branches()?;
+        self.annotate(repo, &mut branches);
+        repo.detect_merged_branches(&mut branches);
+        Ok(branches)
+    }
+
+    /// Fills in what git itself doesn't know about the branches: whether they are
+    /// protected, where to push those git has no push remote for, and their pull
+    /// requests. The repository's own git config adds protected patterns and takes
+    /// precedence for the remote.
+    pub fn annotate(&self, repo: &GitRepo, branches: &mut [Branch]) {
+        let repo_config = repo.config();
+        let protected = self.protected.with(repo_config.protected.clone());
+        let remote = repo_config.remote.as_ref().unwrap_or(&self.remote);
+        let pull_requests = if self.pull_requests.lookup && !branches.is_empty() {
+            self.pull_requests(repo)
+        } else {
+            PullRequests::new()
+        };
+        let remote_urls = if pull_requests.is_empty() {
+            HashMap::new()
+        } else {
+            repo.remote_urls()
+        };
+        for branch in branches {
+            branch.protected = protected.matches(&branch.refname);
+            if branch.push_remote.is_none() {
+                branch.push_remote = Some(remote.clone());
+            }
+            branch.pull_request = pull_request_for(branch, &pull_requests, &remote_urls);
+        }
+    }
+
+    /// The repository's pull requests, from the cache unless it is stale or this run
+    /// fetches. A forge that can't be asked is reported on stderr, and just means no
+    /// pull requests are shown.
+    fn pull_requests(&self, repo: &GitRepo) -> PullRequests {
+        let cache = PullRequestCache::from_env().map(|cache| cache.with_ttl(self.cache_ttl_secs));
+        if !self.fetch
+            && let Some(cached) = cache
+                .as_ref()
+                .and_then(|cache| cache.read_fresh(repo.dir()))
+        {
+            return cached;
+        }
+        match repo.pull_requests() {
+            Ok(pull_requests) => {
+                if let Some(cache) = cache {
+                    let _ = cache.write(repo.dir(), &pull_requests);
+                }
+                pull_requests
+            }
+            Err(err) => {
+                eprintln!(
+                    "Failed to look up pull requests for {}: {err:#}",
+                    repo.dir().display()
+                );
+                PullRequests::new()
+            }
+        }
+    }
+}
+
+/// The pull request from where `branch` is pushed: its name on that remote, in the
+/// repository or fork the remote's URL belongs to. A pull request from someone else's
+/// fork that happens to use the same branch name isn't it.
+fn pull_request_for(
+    branch: &Branch,
+    pull_requests: &PullRequests,
+    remote_urls: &HashMap<String, String>,
+) -> Option<PullRequest> {
+    let (remote, name) = branch.remote_branch();
+    let owner = remote_owner(remote_urls.get(remote)?)?;
+    pull_requests.get(&head_label(owner, name)).cloned()
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::from_config(&Config::default(), false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cleaner::tests::branch;
+    use crate::forge::PullRequestState;
+    use crate::git::{Upstream, UpstreamStatus};
+
+    #[test]
+    fn pull_requests_are_matched_by_where_the_branch_is_pushed() {
+        let pull = |number| PullRequest {
+            number,
+            state: PullRequestState::Merged,
+            url: format!("u{number}"),
+            review: None,
+            head_sha: None,
+        };
+        let pull_requests = PullRequests::from([
+            (head_label("stranger", "patch-1"), pull(1)),
+            (head_label("o", "feature"), pull(2)),
+            (head_label("me", "topic"), pull(3)),
+        ]);
+        let remote_urls = HashMap::from([
+            ("origin".to_string(), "git@github.com:o/r.git".to_string()),
+            (
+                "fork".to_string(),
+                "https://github.com/Me/r.git".to_string(),
+            ),
+        ]);
+        let number = |branch: &Branch| {
+            pull_request_for(branch, &pull_requests, &remote_urls).map(|pull| pull.number)
+        };
+
+        let local = Branch {
+            push_remote: Some("origin".into()),
+            ..branch("patch-1", None)
+        };
+        assert_eq!(number(&local), None);
+
+        let renamed = Branch {
+            upstream: Some(Upstream {
+                name: "origin/feature".into(),
+                remote: "origin".into(),
+                status: UpstreamStatus::Identical,
+                ahead: 0,
+                behind: 0,
+            }),
+            ..branch("local-name", None)
+        };
+        assert_eq!(number(&renamed), Some(2));
+
+        let triangular = Branch {
+            push_remote: Some("fork".into()),
+            push_target: Some(Upstream {
+                name: "fork/topic".into(),
+                remote: "fork".into(),
+                status: UpstreamStatus::Identical,
+                ahead: 0,
+                behind: 0,
+            }),
+            ..branch("topic", Some(UpstreamStatus::LocalIsAheadOfUpstream))
+        };
+        assert_eq!(number(&triangular), Some(3));
+    }
+}
@@ -1,8 +1,29 @@
 use anyhow::{Result, anyhow};
+use clap::ValueEnum;
 use dialoguer::{Select, theme::ColorfulTheme};
 
+use crate::cleaner::BranchAction;
+use crate::git::Branch;
+
 pub trait Prompt: Clone {
     fn select(&self, message: &str, options: &[String]) -> Result<usize>;
+
+    /// Picks what to do with `branch`, from `options` labelling `actions` (`None` for
+    /// custom actions). Left to [`Self::select`] unless the prompt decides by itself.
+    fn select_action(
+        &self,
+        message: &str,
+        _branch: &Branch,
+        _actions: &[Option<BranchAction>],
+        options: &[String],
+    ) -> Result<usize> {
+        self.select(message, options)
+    }
+
+    /// Whether `action`, which is safe enough not to ask about, may go ahead.
+    fn allow_automatic(&self, _branch: &Branch, _action: BranchAction) -> bool {
+        true
+    }
 }
 
 #[derive(Default, Clone)]
@@ -26,14 +47,112 @@ impl Prompt for DialoguerPrompt {
     }
 }
 
+/// Which option a dry run pretends was chosen at each prompt.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AssumedChoice {
+    /// The first, default option, as if Enter was pressed.
+    #[default]
+    Default,
+    /// "Do nothing", which is the last option unless a menu puts it first.
+    Nothing,
+}
+
+/// Answers every prompt with the [`AssumedChoice`], printing the choice, so that a
+/// dry run can follow the same code paths as a real one.
 #[derive(Default, Clone)]
-pub struct DryRunPrompt;
+pub struct DryRunPrompt {
+    assumed_choice: AssumedChoice,
+    quiet: bool,
+}
+
+impl DryRunPrompt {
+    pub fn new(assumed_choice: AssumedChoice) -> Self {
+        Self {
+            assumed_choice,
+            quiet: false,
+        }
+    }
+
+    /// Answers without printing the choice, for output that is read by tools.
+    pub fn quiet(mut self) -> Self {
+        self.quiet = true;
+        self
+    }
+}
+
+impl DryRunPrompt {
+    fn assume(&self, message: &str, options: &[String], index: usize) -> usize {
+        if !self.quiet {
+            println!("[DRY RUN] {message}: {}", options[index]);
+        }
+        index
+    }
+}
 
 impl Prompt for DryRunPrompt {
+    fn select(&self, message: &str, options: &[String]) -> Result<usize> {
+        if options.is_empty() {
+            return Err(anyhow!("no options provided"));
+        }
+        let index = match self.assumed_choice {
+            AssumedChoice::Default => 0,
+            AssumedChoice::Nothing => options.len() - 1,
+        };
+        Ok(self.assume(message, options, index))
+    }
+
+    /// Like [`Self::select`], but skips actions that only show something and custom
+    /// actions, which would otherwise be offered again and again, or run for real.
+    fn select_action(
+        &self,
+        message: &str,
+        _branch: &Branch,
+        actions: &[Option<BranchAction>],
+        options: &[String],
+    ) -> Result<usize> {
+        let mut assumable = (0..options.len()).filter(|&index| {
+            actions
+                .get(index)
+                .copied()
+                .flatten()
+                .is_some_and(|action| !action.only_shows())
+        });
+        let index = match self.assumed_choice {
+            AssumedChoice::Default => assumable.next(),
+            AssumedChoice::Nothing => actions
+                .iter()
+                .position(|action| *action == Some(BranchAction::Nothing))
+                .or_else(|| assumable.next_back()),
+        };
+        let index = index.ok_or_else(|| anyhow!("no action to assume: {message}"))?;
+        Ok(self.assume(message, options, index))
+    }
+}
+
+/// For runs that must not ask anything, such as applying a plan.
+#[derive(Default, Clone)]
+pub struct NoPrompt;
+
+impl Prompt for NoPrompt {
     fn select(&self, message: &str, _options: &[String]) -> Result<usize> {
-        println!("[DRY RUN] Would prompt: {}", message);
-        // Always select "Do nothing" which is typically the last option
-        // This is a fallback - ideally GitCleaner won't call this in dry mode
-        Err(anyhow!("dry run mode - no interactive selections"))
+        Err(anyhow!(
+            "cannot prompt when running non-interactively: {message}"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dry_run_prompt_picks_assumed_choice() -> Result<()> {
+        let options = vec!["Push".to_string(), "Do nothing".to_string()];
+        assert_eq!(DryRunPrompt::default().select("msg", &options)?, 0);
+        assert_eq!(
+            DryRunPrompt::new(AssumedChoice::Nothing).select("msg", &options)?,
+            1
+        );
+        Ok(())
     }
 }